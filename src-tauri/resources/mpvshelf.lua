@@ -3,3 +3,19 @@ local mp = require 'mp'
 -- Set the 'save-position-on-quit' property to true
 mp.set_property("save-position-on-quit", "yes")
 
+-- Bookmark the current position, mpvshelf saves it through its ipc connection
+mp.add_key_binding("ctrl+b", "mpvshelf-bookmark", function()
+    local path = mp.get_property("path")
+    local pos = mp.get_property_number("time-pos")
+    if path == nil or pos == nil then
+        return
+    end
+
+    mp.commandv("script-message", "mpvshelf-bookmark", path, tostring(pos))
+    mp.osd_message("Bookmarked " .. mp.get_property_osd("time-pos"))
+end)
+
+-- Quit & save the position to watch_later with an exit code mpvshelf can tell apart
+mp.add_key_binding("Q", "mpvshelf-quit-watch-later", function()
+    mp.commandv("quit-watch-later", "5")
+end)
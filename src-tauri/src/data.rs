@@ -1,32 +1,41 @@
 use std::fs::File;
+use std::io::Write;
 
 use chrono::{DateTime, Local, NaiveDate};
 use tauri::{command, AppHandle, Manager};
+use zip::write::SimpleFileOptions;
 use zip::ZipWriter;
 use zip_extensions::ZipWriterExtensions;
 
+use crate::database::get_mpv_plugins;
+use crate::error::ExportError;
 use crate::fs::show_in_folder;
 
 ///! Functionality that has to do with exporting the user data
 
+/// where the plugin manifest goes in the exported zip
+const PLUGIN_MANIFEST_PATH: &str = "plugins/manifest.json";
+
 #[command]
-pub fn export_portable_config(handle: AppHandle) {
-    let download_dir = handle.path().download_dir().unwrap();
-    let app_data_dir = handle.path().app_data_dir().unwrap();
+pub fn export_portable_config(handle: AppHandle) -> Result<(), ExportError> {
+    let app_data_dir = handle.path().app_data_dir()?;
     let date_time: DateTime<Local> = Local::now();
     let formatted_date = date_time.format("%Y-%m-%d_%H-%M-%S").to_string();
-    let config_zip_path = app_data_dir
-        .join(format!("portable_config-{}", formatted_date))
-        .with_extension("zip");
     let config_zip_path = app_data_dir
         .join(format!("portable_config-{formatted_date}"))
         .with_extension("zip");
     let portable_config_dir = app_data_dir.join("portable_config");
-    let file = File::create(&config_zip_path).unwrap();
+    // the plugin manifest lives in the db, so it only exists in the export
+    let plugins = get_mpv_plugins(handle.clone())?;
+    let manifest = serde_json::to_string_pretty(&plugins)?;
+    let file = File::create(&config_zip_path)?;
 
-    let zip = ZipWriter::new(file);
-    zip.create_from_directory(&portable_config_dir).unwrap();
+    let mut zip = ZipWriter::new(file);
+    zip.start_file(PLUGIN_MANIFEST_PATH, SimpleFileOptions::default())?;
+    zip.write_all(manifest.as_bytes())?;
+    zip.create_from_directory(&portable_config_dir)?;
     if config_zip_path.exists() {
         show_in_folder(config_zip_path.to_string_lossy().to_string());
     }
+    Ok(())
 }
@@ -1,23 +1,30 @@
 use std::{
-    fs::{self, create_dir, remove_file},
-    io,
+    fs::{self, create_dir, remove_file, File},
+    io::{self, Read, Seek, SeekFrom},
     path::{Path, PathBuf},
     str::FromStr,
     sync::LazyLock,
 };
 
 use chrono::{NaiveDateTime, NaiveTime};
-use data::v1::{MpvSettings, OsFolder, OsFolderKey, OsVideo, OsVideoKey, Settings, User};
+use data::v2::{
+    Bookmark, BookmarkKey, CompletionPolicy, MpvPlugin, MpvSettings, OsFolder, OsFolderKey,
+    OsVideo, OsVideoKey, PlaybackProfile, PlayerBackendKind, Queue, ResumeMode, Settings, Track,
+    TrackSelection, User,
+};
 use hashbrown::HashMap;
 use native_db::*;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use rayon::slice::ParallelSliceMut;
-use tauri::{command, AppHandle, Manager};
+use tauri::{command, AppHandle, Manager, Runtime};
 
 use crate::{
-    error::{DatabaseError, SortTypeError},
+    error::{DatabaseError, MpvSettingsError, SortTypeError},
     fs::join_cover_img_path,
-    misc::get_date_time,
-    mpv::EPISODE_TITLE_REGEX,
+    misc::{format_timestamp, get_date_time},
+    mpv::{MpvPlaybackData, EPISODE_TITLE_REGEX},
+    scan::Scan,
+    watcher::watch_library,
 };
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, SystemTime};
@@ -43,8 +50,16 @@ pub struct FileMetadata {
     pub accessed: Option<SystemTime>,
 
     pub size: Option<u64>,
+
+    /// `<size>-<md5>` of the first & last `FINGERPRINT_CHUNK` bytes,
+    /// to recognise a video after it's moved or renamed.
+    #[serde(default)]
+    pub fingerprint: Option<String>,
 }
 
+/// hashing whole videos would take minutes on a slow drive
+const FINGERPRINT_CHUNK: u64 = 2 * 1024 * 1024;
+
 impl FileMetadata {
     // Constructor to get metadata of a file
     pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
@@ -61,11 +76,42 @@ impl FileMetadata {
                 created,
                 accessed,
                 size,
+                fingerprint: None,
             });
         }
 
         None
     }
+
+    /// only read for videos, the rest of the metadata is cheap to compare on every scan
+    pub fn with_fingerprint(mut self, path: impl AsRef<Path>) -> Self {
+        self.fingerprint = Self::fingerprint(path).ok();
+        self
+    }
+
+    pub fn fingerprint(path: impl AsRef<Path>) -> io::Result<String> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+
+        let mut context = md5::Context::new();
+        let mut chunk = Vec::with_capacity(FINGERPRINT_CHUNK as usize);
+        (&mut file)
+            .take(FINGERPRINT_CHUNK)
+            .read_to_end(&mut chunk)?;
+        context.consume(&chunk);
+        if size > FINGERPRINT_CHUNK {
+            // don't hash the middle of small files twice
+            let tail_start = size
+                .saturating_sub(FINGERPRINT_CHUNK)
+                .max(FINGERPRINT_CHUNK);
+            file.seek(SeekFrom::Start(tail_start))?;
+            chunk.clear();
+            file.take(FINGERPRINT_CHUNK).read_to_end(&mut chunk)?;
+            context.consume(&chunk);
+        }
+
+        Ok(format!("{size}-{:x}", context.compute()))
+    }
 }
 
 // Serialize SystemTime as u64 (seconds since epoch)
@@ -96,8 +142,11 @@ pub mod data {
     use native_model::{native_model, Model};
     use serde::{Deserialize, Serialize};
 
+    /// the models of the last release, only kept to migrate from.
+    /// bincode isn't self-describing, so every field change needs a new version.
     pub mod v1 {
-        use crate::database::FileMetadata;
+        use crate::database::{deserialize_system_time, serialize_system_time};
+        use std::time::SystemTime;
 
         use super::*;
 
@@ -143,32 +192,538 @@ pub mod data {
             pub title: String,
             pub cover_img_path: Option<String>,
             pub watched: bool,
+            pub duration: u64,
+            pub position: u64,
+            pub metadata: Option<FileMetadata>,
+            pub update_date: String,
+            pub update_time: String,
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug, Eq, Hash, PartialEq)]
+        pub struct FileMetadata {
+            #[serde(
+                serialize_with = "serialize_system_time",
+                deserialize_with = "deserialize_system_time"
+            )]
+            pub created: Option<SystemTime>,
+            #[serde(
+                serialize_with = "serialize_system_time",
+                deserialize_with = "deserialize_system_time"
+            )]
+            pub modified: Option<SystemTime>,
+            #[serde(
+                serialize_with = "serialize_system_time",
+                deserialize_with = "deserialize_system_time"
+            )]
+            pub accessed: Option<SystemTime>,
+            pub size: Option<u64>,
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug)]
+        #[native_model(id = 5, version = 1)]
+        #[native_db]
+        pub struct Settings {
+            #[primary_key]
+            pub user_id: String,
+            pub mpv_settings: MpvSettings,
+            pub update_date: String,
+            pub update_time: String,
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug)]
+        pub struct MpvSettings {
+            pub exe_path: Option<String>,
+            pub config_path: Option<String>,
+            pub plugins_path: Option<String>,
+            pub autoplay: bool,
+        }
+    }
+
+    /// the current models.
+    /// `#[serde(default)]` only fills in the json sent by the frontend,
+    /// records are migrated from `v1` in `migrate_database`.
+    pub mod v2 {
+        use crate::database::{deserialize_system_time, serialize_system_time, FileMetadata};
+        use std::time::SystemTime;
+
+        use super::*;
+
+        #[derive(Serialize, Deserialize, Clone, Debug)]
+        #[native_model(id = 1, version = 2, from = v1::User)]
+        #[native_db]
+        pub struct User {
+            #[primary_key]
+            pub id: String,
+            #[secondary_key(unique)]
+            pub username: String,
+            pub settings: Settings,
+            pub last_watched_video: Option<OsVideo>,
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug)]
+        #[native_model(id = 3, version = 2, from = v1::OsFolder)]
+        #[native_db]
+        pub struct OsFolder {
+            #[secondary_key]
+            pub user_id: String,
+            #[primary_key]
+            pub path: String,
+            pub title: String,
+            #[secondary_key]
+            pub parent_path: Option<String>,
+            pub last_watched_video: Option<OsVideo>,
+            pub cover_img_path: Option<String>,
+            /// * in seconds.
+            /// length of the credits in this folder's videos,
+            /// cut off the duration before checking the `CompletionPolicy`
+            #[serde(default)]
+            pub credits_offset: Option<u64>,
+            /// merged over the user's `MpvSettings`,
+            /// & inherited by child folders
+            #[serde(default)]
+            pub profile: Option<PlaybackProfile>,
+            /// the tracks last picked in this folder,
+            /// used as the default for its other videos
+            #[serde(default)]
+            pub tracks: Option<TrackSelection>,
+            /// only audio files directly inside it
+            #[serde(default)]
+            pub audiobook: bool,
+            pub update_date: String,
+            pub update_time: String,
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug)]
+        #[native_model(id = 4, version = 2, from = v1::OsVideo)]
+        #[native_db(secondary_key(fingerprint_key -> String, optional))]
+        pub struct OsVideo {
+            #[secondary_key]
+            pub user_id: String,
+            #[secondary_key]
+            pub main_folder_path: String,
+            #[primary_key]
+            pub path: String,
+            pub title: String,
+            pub cover_img_path: Option<String>,
+            pub watched: bool,
+            /// started, but not far enough to count as watched
+            #[serde(default)]
+            pub in_progress: bool,
             /// * in seconds.
             /// `19:45:12` = `1185` min.
             pub duration: u64,
             pub position: u64,
+            /// when `position` last changed, `update_date` changes with any update
+            #[serde(
+                default,
+                serialize_with = "serialize_system_time",
+                deserialize_with = "deserialize_system_time"
+            )]
+            pub position_time: Option<SystemTime>,
             pub metadata: Option<FileMetadata>,
+            /// external subtitle files matched by filename
+            #[serde(default)]
+            pub subtitles: Vec<Subtitle>,
+            /// the tracks selected when this video was last played
+            #[serde(default)]
+            pub tracks: Option<TrackSelection>,
+            /// embedded chapters, as read by ffmpeg
+            #[serde(default)]
+            pub chapters: Vec<Chapter>,
+            /// the playback speed last used, only remembered for audiobooks
+            #[serde(default)]
+            pub speed: Option<f64>,
             pub update_date: String,
             pub update_time: String,
         }
 
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        pub struct Chapter {
+            pub title: Option<String>,
+            /// * in seconds.
+            pub start: u64,
+            pub end: u64,
+        }
+
+        /// unset fields fall back to the parent folder's profile,
+        /// and then to the user's settings.
+        #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+        pub struct PlaybackProfile {
+            /// `--alang`, eg: `jpn,ja`
+            pub audio_lang: Option<String>,
+            /// `--slang`, eg: `eng,en`
+            pub sub_lang: Option<String>,
+            /// `Some(false)` turns subtitles off
+            pub subtitles: Option<bool>,
+            pub speed: Option<f64>,
+            pub volume: Option<u32>,
+            /// appended last, so they override everything else
+            #[serde(default)]
+            pub extra_args: Vec<String>,
+            pub autoplay: Option<bool>,
+        }
+
+        /// unset fields are unknown, not turned off
+        #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+        pub struct TrackSelection {
+            pub aid: Option<Track>,
+            pub sid: Option<Track>,
+            pub audio_lang: Option<String>,
+            pub sub_lang: Option<String>,
+        }
+
+        /// an mpv `aid`/`sid`
+        #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+        #[serde(rename_all = "snake_case")]
+        pub enum Track {
+            Id(i64),
+            Off,
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        pub struct Subtitle {
+            pub path: String,
+            /// eg: `ja` from `<video>.ja.srt`
+            pub lang: Option<String>,
+        }
+
         #[derive(Serialize, Deserialize, Clone, Debug)]
-        #[native_model(id = 5, version = 1)]
+        #[native_model(id = 5, version = 2, from = v1::Settings)]
         #[native_db]
         pub struct Settings {
             #[primary_key]
             pub user_id: String,
             pub mpv_settings: MpvSettings,
+            /// merges mpv's watch_later positions after every scan when set
+            #[serde(default)]
+            pub watch_later_import: Option<WatchLaterPolicy>,
+            #[serde(default)]
+            pub completion: CompletionPolicy,
+            /// gitignore patterns matched against every file & folder name while scanning,
+            /// on top of each folder's `.mpvshelfignore`
+            #[serde(default = "default_ignore_patterns")]
+            pub ignore_patterns: Vec<String>,
             pub update_date: String,
             pub update_time: String,
         }
 
+        /// when a video counts as watched
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        #[serde(rename_all = "snake_case")]
+        pub enum CompletionPolicy {
+            /// after this percentage of the duration
+            Percentage(u8),
+            /// * in seconds.
+            /// within this many seconds of the end
+            FromEnd(u64),
+        }
+
+        impl Default for CompletionPolicy {
+            fn default() -> Self {
+                Self::Percentage(90)
+            }
+        }
+
+        /// which side wins when both the database & mpv's watch_later have a position
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        #[serde(rename_all = "snake_case")]
+        pub enum WatchLaterPolicy {
+            NewestWins,
+            DbWins,
+        }
+
         #[derive(Serialize, Deserialize, Clone, Debug)]
         pub struct MpvSettings {
             pub exe_path: Option<String>,
             pub config_path: Option<String>,
             pub plugins_path: Option<String>,
             pub autoplay: bool,
+            /// how often (in seconds) the playback progress
+            /// is saved while mpv is still running
+            #[serde(default = "default_checkpoint_interval")]
+            pub checkpoint_interval: u64,
+            #[serde(default)]
+            pub resume: ResumeMode,
+            /// positions within this many seconds of the end
+            /// restart the video from the beginning instead
+            #[serde(default = "default_resume_tail")]
+            pub resume_tail: u64,
+            /// the player to play with, mpv unless set
+            #[serde(default)]
+            pub backend: PlayerBackendKind,
+            /// resume videos with chapters from the start of the current chapter,
+            /// or audiobook files from their start
+            #[serde(default)]
+            pub resume_chapter_start: bool,
+        }
+
+        pub fn default_ignore_patterns() -> Vec<String> {
+            [
+                ".Trash*", "Sample/", "Samples/", "Extras/", "NCOP*", "NCED*",
+            ]
+            .map(String::from)
+            .to_vec()
+        }
+
+        fn default_checkpoint_interval() -> u64 {
+            30
+        }
+
+        fn default_resume_tail() -> u64 {
+            30
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+        #[serde(rename_all = "snake_case")]
+        pub enum PlayerBackendKind {
+            #[default]
+            Mpv,
+            /// any other player, see `player::CommandTemplateBackend`
+            CommandTemplate(Vec<String>),
+        }
+
+        /// a script or script-opts file installed into `portable_config/plugins`
+        #[derive(Serialize, Deserialize, Clone, Debug)]
+        #[native_model(id = 6, version = 1)]
+        #[native_db]
+        pub struct MpvPlugin {
+            /// file or directory name, as installed
+            #[primary_key]
+            pub name: String,
+            pub kind: MpvPluginKind,
+            pub source_path: String,
+            pub installed_path: String,
+            pub enabled: bool,
+            pub install_date: String,
+            pub install_time: String,
+        }
+
+        /// the user's "Up Next" queue, videos from any folder
+        #[derive(Serialize, Deserialize, Clone, Debug)]
+        #[native_model(id = 8, version = 1)]
+        #[native_db]
+        pub struct Queue {
+            #[primary_key]
+            pub user_id: String,
+            /// `OsVideo` paths, in playing order
+            pub video_paths: Vec<String>,
+            pub update_date: String,
+            pub update_time: String,
+        }
+
+        /// a named timestamp inside a video
+        #[derive(Serialize, Deserialize, Clone, Debug)]
+        #[native_model(id = 7, version = 1)]
+        #[native_db(primary_key(key -> String))]
+        pub struct Bookmark {
+            #[secondary_key]
+            pub video_path: String,
+            /// * in seconds.
+            pub timestamp: u64,
+            pub name: String,
+            pub note: String,
+            pub create_date: String,
+            pub create_time: String,
+        }
+
+        impl Bookmark {
+            /// `<video_path>#<timestamp>`, one bookmark per second of a video
+            fn key(&self) -> String {
+                format!("{}#{}", self.video_path, self.timestamp)
+            }
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        #[serde(rename_all = "snake_case")]
+        pub enum MpvPluginKind {
+            /// a lua/js file, or a script dir with a `main.lua`/`main.js`
+            Script,
+            /// `<script name>.conf` file
+            ScriptOpts,
+        }
+
+        /// whether `play_video` starts from the stored `OsVideo.position`
+        #[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+        #[serde(rename_all = "lowercase")]
+        pub enum ResumeMode {
+            #[default]
+            Always,
+            Ask,
+            Never,
+        }
+
+        impl From<v1::User> for User {
+            fn from(user: v1::User) -> Self {
+                Self {
+                    id: user.id,
+                    username: user.username,
+                    settings: user.settings.into(),
+                    last_watched_video: user.last_watched_video.map(OsVideo::from),
+                }
+            }
+        }
+
+        impl From<User> for v1::User {
+            fn from(user: User) -> Self {
+                Self {
+                    id: user.id,
+                    username: user.username,
+                    settings: user.settings.into(),
+                    last_watched_video: user.last_watched_video.map(v1::OsVideo::from),
+                }
+            }
+        }
+
+        impl From<v1::OsFolder> for OsFolder {
+            fn from(folder: v1::OsFolder) -> Self {
+                Self {
+                    user_id: folder.user_id,
+                    path: folder.path,
+                    title: folder.title,
+                    parent_path: folder.parent_path,
+                    last_watched_video: folder.last_watched_video.map(OsVideo::from),
+                    cover_img_path: folder.cover_img_path,
+                    credits_offset: None,
+                    profile: None,
+                    tracks: None,
+                    // set by the next scan
+                    audiobook: false,
+                    update_date: folder.update_date,
+                    update_time: folder.update_time,
+                }
+            }
+        }
+
+        impl From<OsFolder> for v1::OsFolder {
+            fn from(folder: OsFolder) -> Self {
+                Self {
+                    user_id: folder.user_id,
+                    path: folder.path,
+                    title: folder.title,
+                    parent_path: folder.parent_path,
+                    last_watched_video: folder.last_watched_video.map(v1::OsVideo::from),
+                    cover_img_path: folder.cover_img_path,
+                    update_date: folder.update_date,
+                    update_time: folder.update_time,
+                }
+            }
+        }
+
+        impl From<v1::OsVideo> for OsVideo {
+            fn from(vid: v1::OsVideo) -> Self {
+                Self {
+                    user_id: vid.user_id,
+                    main_folder_path: vid.main_folder_path,
+                    path: vid.path,
+                    title: vid.title,
+                    cover_img_path: vid.cover_img_path,
+                    watched: vid.watched,
+                    in_progress: !vid.watched && vid.position > 0,
+                    duration: vid.duration,
+                    position: vid.position,
+                    position_time: None,
+                    metadata: vid.metadata.map(FileMetadata::from),
+                    subtitles: Vec::new(),
+                    tracks: None,
+                    chapters: Vec::new(),
+                    speed: None,
+                    update_date: vid.update_date,
+                    update_time: vid.update_time,
+                }
+            }
+        }
+
+        impl From<OsVideo> for v1::OsVideo {
+            fn from(vid: OsVideo) -> Self {
+                Self {
+                    user_id: vid.user_id,
+                    main_folder_path: vid.main_folder_path,
+                    path: vid.path,
+                    title: vid.title,
+                    cover_img_path: vid.cover_img_path,
+                    watched: vid.watched,
+                    duration: vid.duration,
+                    position: vid.position,
+                    metadata: vid.metadata.map(v1::FileMetadata::from),
+                    update_date: vid.update_date,
+                    update_time: vid.update_time,
+                }
+            }
+        }
+
+        impl From<v1::FileMetadata> for FileMetadata {
+            fn from(metadata: v1::FileMetadata) -> Self {
+                Self {
+                    created: metadata.created,
+                    modified: metadata.modified,
+                    accessed: metadata.accessed,
+                    size: metadata.size,
+                    fingerprint: None,
+                }
+            }
+        }
+
+        impl From<FileMetadata> for v1::FileMetadata {
+            fn from(metadata: FileMetadata) -> Self {
+                Self {
+                    created: metadata.created,
+                    modified: metadata.modified,
+                    accessed: metadata.accessed,
+                    size: metadata.size,
+                }
+            }
+        }
+
+        impl From<v1::Settings> for Settings {
+            fn from(settings: v1::Settings) -> Self {
+                Self {
+                    user_id: settings.user_id,
+                    mpv_settings: settings.mpv_settings.into(),
+                    watch_later_import: None,
+                    completion: CompletionPolicy::default(),
+                    ignore_patterns: default_ignore_patterns(),
+                    update_date: settings.update_date,
+                    update_time: settings.update_time,
+                }
+            }
+        }
+
+        impl From<Settings> for v1::Settings {
+            fn from(settings: Settings) -> Self {
+                Self {
+                    user_id: settings.user_id,
+                    mpv_settings: settings.mpv_settings.into(),
+                    update_date: settings.update_date,
+                    update_time: settings.update_time,
+                }
+            }
+        }
+
+        impl From<v1::MpvSettings> for MpvSettings {
+            fn from(mpv_settings: v1::MpvSettings) -> Self {
+                Self {
+                    exe_path: mpv_settings.exe_path,
+                    config_path: mpv_settings.config_path,
+                    plugins_path: mpv_settings.plugins_path,
+                    autoplay: mpv_settings.autoplay,
+                    checkpoint_interval: default_checkpoint_interval(),
+                    resume: ResumeMode::default(),
+                    resume_tail: default_resume_tail(),
+                    backend: PlayerBackendKind::default(),
+                    resume_chapter_start: false,
+                }
+            }
+        }
+
+        impl From<MpvSettings> for v1::MpvSettings {
+            fn from(mpv_settings: MpvSettings) -> Self {
+                Self {
+                    exe_path: mpv_settings.exe_path,
+                    config_path: mpv_settings.config_path,
+                    plugins_path: mpv_settings.plugins_path,
+                    autoplay: mpv_settings.autoplay,
+                }
+            }
         }
     }
 }
@@ -180,6 +735,11 @@ impl Default for Settings {
             config_path: None,
             plugins_path: None,
             autoplay: true,
+            checkpoint_interval: 30,
+            resume: ResumeMode::Always,
+            resume_tail: 30,
+            backend: PlayerBackendKind::Mpv,
+            resume_chapter_start: false,
         };
 
         let (update_date, update_time) = get_date_time();
@@ -187,21 +747,161 @@ impl Default for Settings {
         Self {
             user_id: "1".into(),
             mpv_settings,
+            watch_later_import: None,
+            completion: CompletionPolicy::default(),
+            ignore_patterns: data::v2::default_ignore_patterns(),
             update_date,
             update_time,
         }
     }
 }
 
+impl CompletionPolicy {
+    /// `credits_offset` is cut off the end of the duration first
+    pub fn is_complete(&self, position: u64, duration: u64, credits_offset: u64) -> bool {
+        if duration == 0 {
+            return false;
+        }
+        let duration = duration.saturating_sub(credits_offset);
+
+        match self {
+            CompletionPolicy::Percentage(percent) => position * 100 >= duration * *percent as u64,
+            CompletionPolicy::FromEnd(secs) => position + secs >= duration,
+        }
+    }
+}
+
+impl PlaybackProfile {
+    /// fills in everything this profile leaves unset from the `parent`'s profile
+    pub fn inherit(self, parent: &PlaybackProfile) -> PlaybackProfile {
+        let mut extra_args = parent.extra_args.clone();
+        extra_args.extend(self.extra_args);
+
+        PlaybackProfile {
+            audio_lang: self.audio_lang.or_else(|| parent.audio_lang.clone()),
+            sub_lang: self.sub_lang.or_else(|| parent.sub_lang.clone()),
+            subtitles: self.subtitles.or(parent.subtitles),
+            speed: self.speed.or(parent.speed),
+            volume: self.volume.or(parent.volume),
+            extra_args,
+            autoplay: self.autoplay.or(parent.autoplay),
+        }
+    }
+
+    pub fn to_mpv_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(audio_lang) = &self.audio_lang {
+            args.push(format!("--alang={audio_lang}"));
+        }
+        if let Some(sub_lang) = &self.sub_lang {
+            args.push(format!("--slang={sub_lang}"));
+        }
+        if self.subtitles == Some(false) {
+            args.push(String::from("--sid=no"));
+        }
+        if let Some(speed) = self.speed {
+            args.push(format!("--speed={speed}"));
+        }
+        if let Some(volume) = self.volume {
+            args.push(format!("--volume={volume}"));
+        }
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+}
+
+impl TrackSelection {
+    /// overwrites every field `other` knows about
+    pub fn update(&mut self, other: &TrackSelection) {
+        self.aid = other.aid.or(self.aid);
+        self.sid = other.sid.or(self.sid);
+        if other.audio_lang.is_some() {
+            self.audio_lang = other.audio_lang.clone();
+        }
+        if other.sub_lang.is_some() {
+            self.sub_lang = other.sub_lang.clone();
+        }
+    }
+
+    /// mpv args to use this selection as a folder default.
+    /// track ids differ between files, so only the languages
+    /// & whether subtitles are off carry over.
+    pub fn default_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(audio_lang) = &self.audio_lang {
+            args.push(format!("--alang={audio_lang}"));
+        }
+        if self.sid == Some(Track::Off) {
+            args.push(String::from("--sid=no"));
+        } else if let Some(sub_lang) = &self.sub_lang {
+            args.push(format!("--slang={sub_lang}"));
+        }
+        args
+    }
+}
+
+impl MpvSettings {
+    /// `--config-dir`, defaults to the app's `portable_config`
+    pub fn config_dir(&self, app_data_dir: &Path) -> PathBuf {
+        match &self.config_path {
+            Some(config_path) => PathBuf::from(config_path),
+            None => app_data_dir.join("portable_config"),
+        }
+    }
+
+    /// every script in this dir is loaded into mpv
+    pub fn plugins_dir(&self, app_data_dir: &Path) -> PathBuf {
+        match &self.plugins_path {
+            Some(plugins_path) => PathBuf::from(plugins_path),
+            None => app_data_dir.join("portable_config").join("plugins"),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), MpvSettingsError> {
+        if let Some(config_path) = &self.config_path {
+            let path = Path::new(config_path);
+            if !path.exists() {
+                return Err(MpvSettingsError::ConfigPathNotFound(config_path.clone()));
+            }
+            if !path.is_dir() {
+                return Err(MpvSettingsError::ConfigPathNotDir(config_path.clone()));
+            }
+        }
+        if let Some(plugins_path) = &self.plugins_path {
+            let path = Path::new(plugins_path);
+            if !path.exists() {
+                return Err(MpvSettingsError::PluginsPathNotFound(plugins_path.clone()));
+            }
+            if !path.is_dir() {
+                return Err(MpvSettingsError::PluginsPathNotDir(plugins_path.clone()));
+            }
+        }
+        if self.backend == PlayerBackendKind::CommandTemplate(Vec::new()) {
+            return Err(MpvSettingsError::EmptyCommandTemplate);
+        }
+
+        Ok(())
+    }
+}
+
 static DBMODELS: LazyLock<Models> = LazyLock::new(|| {
     let mut models = Models::new();
     models.define::<data::v1::User>().unwrap();
     models.define::<data::v1::OsFolder>().unwrap();
     models.define::<data::v1::OsVideo>().unwrap();
+    models.define::<data::v2::User>().unwrap();
+    models.define::<data::v2::OsFolder>().unwrap();
+    models.define::<data::v2::OsVideo>().unwrap();
+    models.define::<data::v2::MpvPlugin>().unwrap();
+    models.define::<data::v2::Bookmark>().unwrap();
+    models.define::<data::v2::Queue>().unwrap();
     models
 });
 
-pub fn init_database(app_data_dir: &PathBuf, handle: &AppHandle) -> Result<(), db_type::Error> {
+pub fn init_database<R: Runtime>(
+    app_data_dir: &PathBuf,
+    handle: &AppHandle<R>,
+) -> Result<(), db_type::Error> {
     if !app_data_dir.exists() {
         create_dir(app_data_dir)?;
     }
@@ -223,12 +923,24 @@ pub fn init_database(app_data_dir: &PathBuf, handle: &AppHandle) -> Result<(), d
         create_dir(&plugins_dir).unwrap();
     }
     let db_path = app_data_dir.join("main").with_extension("rdb");
-    Builder::new().create(&DBMODELS, &db_path)?;
+    let db = Builder::new().create(&DBMODELS, &db_path)?;
+    migrate_database(&db)?;
 
-    handle.manage(db_path);
+    // redb locks the file while it's open,
+    // so every thread shares this one instance
+    handle.manage(db);
     Ok(())
 }
 
+/// moves the records written by older releases to the current models
+fn migrate_database(db: &Database) -> Result<(), db_type::Error> {
+    let rwtx = db.rw_transaction()?;
+    rwtx.migrate::<data::v2::User>()?;
+    rwtx.migrate::<data::v2::OsFolder>()?;
+    rwtx.migrate::<data::v2::OsVideo>()?;
+    rwtx.commit()
+}
+
 pub trait HasPath {
     fn path(&self) -> &str;
 }
@@ -306,6 +1018,13 @@ impl OsFolder {
 }
 
 impl OsVideo {
+    /// the 0-based index of the chapter playing at `position`
+    pub fn chapter_at(&self, position: u64) -> Option<usize> {
+        self.chapters
+            .iter()
+            .rposition(|chapter| chapter.start <= position)
+    }
+
     pub fn new(
         user_id: String,
         super_parent: Option<impl AsRef<str>>,
@@ -326,7 +1045,7 @@ impl OsVideo {
             .to_string_lossy()
             .to_string();
 
-        let metadata = FileMetadata::from_path(&path);
+        let metadata = FileMetadata::from_path(&path).map(|md| md.with_fingerprint(&path));
 
         let cover_img_path =
             join_cover_img_path(super_parent, &main_folder_path, &path, app_data_dir)?;
@@ -341,9 +1060,15 @@ impl OsVideo {
             title,
             cover_img_path: Some(cover_img_path),
             watched: false,
+            in_progress: false,
             duration: 0,
             position: 0,
+            position_time: None,
             metadata,
+            subtitles: Vec::new(),
+            tracks: None,
+            chapters: Vec::new(),
+            speed: None,
             update_date,
             update_time,
         };
@@ -366,6 +1091,26 @@ impl OsVideo {
         }
     }
 
+    pub fn fingerprint(&self) -> Option<&str> {
+        self.metadata.as_ref()?.fingerprint.as_deref()
+    }
+
+    /// the `fingerprint` secondary key
+    fn fingerprint_key(&self) -> Option<String> {
+        self.fingerprint().map(String::from)
+    }
+
+    /// keeps what the user did with a video that was moved or renamed,
+    /// everything else is read from the file again.
+    pub fn carry_over(&mut self, old: &OsVideo) {
+        self.watched = old.watched;
+        self.in_progress = old.in_progress;
+        self.position = old.position;
+        self.position_time = old.position_time;
+        self.tracks = old.tracks.clone();
+        self.speed = old.speed;
+    }
+
     fn _delete_cover_img(&self) -> io::Result<()> {
         if let Some(path) = &self.cover_img_path {
             remove_file(path)?
@@ -373,6 +1118,32 @@ impl OsVideo {
         Ok(())
     }
 
+    /// a video that was never scanned or played, for tests
+    #[cfg(test)]
+    pub fn stub(main_folder_path: &str, path: &str) -> Self {
+        OsVideo {
+            user_id: "1".into(),
+            main_folder_path: main_folder_path.into(),
+            path: path.into(),
+            title: Path::new(path)
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().to_string()),
+            cover_img_path: None,
+            watched: false,
+            in_progress: false,
+            duration: 0,
+            position: 0,
+            position_time: None,
+            metadata: None,
+            subtitles: Vec::new(),
+            tracks: None,
+            chapters: Vec::new(),
+            speed: None,
+            update_date: String::new(),
+            update_time: String::new(),
+        }
+    }
+
     pub fn as_hashmap<I, F, K>(iter: I, key_fn: F) -> HashMap<K, Self>
     where
         I: IntoIterator<Item = Self>,
@@ -443,8 +1214,7 @@ pub fn get_os_folders(
     user_id: String,
     sort_type: String,
 ) -> Result<Vec<OsFolder>, DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().create(&DBMODELS, db_path)?;
+    let db = handle.state::<Database>();
 
     let rtx = db.r_transaction()?;
     let mut folders: Vec<OsFolder> = rtx
@@ -467,12 +1237,11 @@ pub fn get_os_folders(
 }
 
 #[command]
-pub fn get_os_folder_by_path(
-    handle: AppHandle,
+pub fn get_os_folder_by_path<R: Runtime>(
+    handle: AppHandle<R>,
     folder_path: String,
 ) -> Result<OsFolder, DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().create(&DBMODELS, db_path)?;
+    let db = handle.state::<Database>();
 
     let rtx = db.r_transaction()?;
     let folder: Option<OsFolder> = rtx.get().primary(folder_path.as_str())?;
@@ -492,8 +1261,7 @@ pub fn get_os_folders_by_path(
     parent_path: String,
     sort_type: String,
 ) -> Result<Vec<OsFolder>, DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().create(&DBMODELS, db_path)?;
+    let db = handle.state::<Database>();
 
     let rtx = db.r_transaction()?;
     let mut folders: Vec<OsFolder> = rtx
@@ -514,13 +1282,46 @@ pub fn get_os_folders_by_path(
     Ok(folders)
 }
 
+/// merges the folder's profile with the profiles of all its parent folders
+pub fn resolve_playback_profile(
+    handle: &AppHandle,
+    os_folder: &OsFolder,
+) -> Result<PlaybackProfile, DatabaseError> {
+    let db = handle.state::<Database>();
+    let rtx = db.r_transaction()?;
+
+    let mut profile = os_folder.profile.clone().unwrap_or_default();
+    let mut parent_path = os_folder.parent_path.clone();
+    while let Some(path) = parent_path {
+        let parent: Option<OsFolder> = rtx.get().primary(path.as_str())?;
+        let Some(parent) = parent else {
+            break;
+        };
+        if let Some(parent_profile) = &parent.profile {
+            profile = profile.inherit(parent_profile);
+        }
+        parent_path = parent.parent_path;
+    }
+
+    Ok(profile)
+}
+
+/// returns the profile `play_video` would use for this folder
 #[command]
-pub fn update_os_folders(
+pub fn get_playback_profile(
     handle: AppHandle,
-    os_folders: Vec<OsFolder>,
+    folder_path: String,
+) -> Result<PlaybackProfile, DatabaseError> {
+    let os_folder = get_os_folder_by_path(handle.clone(), folder_path)?;
+    resolve_playback_profile(&handle, &os_folder)
+}
+
+#[command]
+pub fn update_os_folders<R: Runtime>(
+    handle: AppHandle<R>,
+    os_folders: Vec<OsFolder>,
 ) -> Result<(), DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().open(&DBMODELS, db_path)?;
+    let db = handle.state::<Database>();
     let rwtx = db.rw_transaction()?;
     let (date, time) = get_date_time();
 
@@ -537,10 +1338,54 @@ pub fn update_os_folders(
     Ok(())
 }
 
+/// scans rebuild folders from scratch,
+/// so carry over what was set on them since they were first added.
+pub fn preserve_os_folder_state(
+    handle: &AppHandle,
+    os_folders: &mut [OsFolder],
+) -> Result<(), DatabaseError> {
+    let db = handle.state::<Database>();
+    let rtx = db.r_transaction()?;
+
+    for folder in os_folders.iter_mut() {
+        let old: Option<OsFolder> = rtx.get().primary(folder.path.as_str())?;
+        if let Some(old) = old {
+            if old.last_watched_video.is_some() {
+                folder.last_watched_video = old.last_watched_video;
+            }
+            folder.credits_offset = old.credits_offset;
+            folder.profile = old.profile;
+            folder.tracks = old.tracks;
+        }
+    }
+
+    Ok(())
+}
+
+/// keeps the progress of videos that are already in the library
+pub fn preserve_os_video_state<R: Runtime>(
+    handle: &AppHandle<R>,
+    os_videos: &mut [OsVideo],
+) -> Result<(), DatabaseError> {
+    let db = handle.state::<Database>();
+    let rtx = db.r_transaction()?;
+
+    for vid in os_videos.iter_mut() {
+        let old: Option<OsVideo> = rtx.get().primary(vid.path.as_str())?;
+        if let Some(old) = old {
+            vid.carry_over(&old);
+        }
+    }
+
+    Ok(())
+}
+
 #[command]
-pub fn update_os_videos(handle: AppHandle, os_videos: Vec<OsVideo>) -> Result<(), DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().open(&DBMODELS, db_path)?;
+pub fn update_os_videos<R: Runtime>(
+    handle: AppHandle<R>,
+    os_videos: Vec<OsVideo>,
+) -> Result<(), DatabaseError> {
+    let db = handle.state::<Database>();
     let rtx = db.rw_transaction()?;
     let (date, time) = get_date_time();
 
@@ -563,14 +1408,94 @@ pub fn update_os_videos(handle: AppHandle, os_videos: Vec<OsVideo>) -> Result<()
     Ok(())
 }
 
+/// checkpoints the video mpv is playing, in one transaction.
+/// the video, its folder & the user are reloaded first & only their playback fields are written,
+/// so settings & library changes made while mpv is running aren't reverted.
+/// returns the saved video, or `None` if it's no longer in the library.
+pub fn save_playback_progress<R: Runtime>(
+    handle: &AppHandle<R>,
+    user_id: &str,
+    data: &MpvPlaybackData,
+    speed: Option<f64>,
+) -> Result<Option<OsVideo>, DatabaseError> {
+    let db = handle.state::<Database>();
+    let rwtx = db.rw_transaction()?;
+
+    let Some(mut vid) = rwtx
+        .get()
+        .primary::<OsVideo>(data.last_video_path.as_str())?
+    else {
+        return Ok(None);
+    };
+    let folder: Option<OsFolder> = rwtx.get().primary(vid.main_folder_path.as_str())?;
+    let user: Option<User> = rwtx.get().primary(user_id)?;
+
+    if vid.position != data.last_video_position {
+        vid.position = data.last_video_position;
+        vid.position_time = Some(SystemTime::now());
+    }
+    // mpv hasn't reported the duration yet right after a file change
+    if data.last_video_duration > 0 {
+        vid.duration = data.last_video_duration;
+    }
+
+    let credits_offset = folder
+        .as_ref()
+        .and_then(|folder| folder.credits_offset)
+        .unwrap_or(0);
+    let is_complete = user.as_ref().is_some_and(|user| {
+        user.settings
+            .completion
+            .is_complete(vid.position, vid.duration, credits_offset)
+    });
+    if is_complete {
+        vid.watched = true;
+        vid.in_progress = false;
+    } else if vid.position > 0 && !vid.watched {
+        vid.in_progress = true;
+    }
+    let audiobook = folder.as_ref().is_some_and(|folder| folder.audiobook);
+    if audiobook && speed.is_some() {
+        vid.speed = speed;
+    }
+    let has_tracks = data.tracks != TrackSelection::default();
+    if has_tracks {
+        vid.tracks
+            .get_or_insert_with(Default::default)
+            .update(&data.tracks);
+    }
+    let (date, time) = get_date_time();
+    vid.update_date = date;
+    vid.update_time = time;
+    rwtx.upsert(vid.clone())?;
+
+    if let Some(mut folder) = folder {
+        folder.last_watched_video = Some(vid.clone());
+        if has_tracks {
+            folder
+                .tracks
+                .get_or_insert_with(Default::default)
+                .update(&data.tracks);
+        }
+        rwtx.upsert(folder)?;
+    }
+    if let Some(mut user) = user {
+        user.last_watched_video = Some(vid.clone());
+        rwtx.upsert(user)?;
+    }
+
+    rwtx.commit()?;
+
+    Ok(Some(vid))
+}
+
 #[command]
 pub fn get_os_videos(
     handle: AppHandle,
     main_folder_path: String,
     sort_type: String,
 ) -> Result<Vec<OsVideo>, DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().create(&DBMODELS, db_path)?;
+    let db = handle.state::<Database>();
 
     let rtx = db.r_transaction()?;
     let mut videos: Vec<OsVideo> = rtx
@@ -596,15 +1521,49 @@ pub fn get_os_videos(
     Ok(videos)
 }
 
+pub fn get_os_videos_by_user(
+    handle: &AppHandle,
+    user_id: &str,
+) -> Result<Vec<OsVideo>, DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rtx = db.r_transaction()?;
+    let videos: Vec<OsVideo> = rtx
+        .scan()
+        .secondary(OsVideoKey::user_id)?
+        .start_with(user_id)?
+        .take_while(|e: &Result<OsVideo, db_type::Error>| match e {
+            Ok(vid) => vid.user_id == user_id,
+            Err(_) => false,
+        })
+        .try_collect()?;
+
+    Ok(videos)
+}
+
 #[command]
 pub fn delete_os_folders(
     handle: AppHandle,
     os_folders: Vec<OsFolder>,
+    user: Option<User>,
+) -> Result<(), DatabaseError> {
+    remove_os_folders(&handle, os_folders, user)?;
+
+    if let Err(e) = watch_library(&handle) {
+        eprintln!("failed to update the library watcher: {e}");
+    }
+
+    Ok(())
+}
+
+/// removes the folders along with their child folders, videos & bookmarks
+pub fn remove_os_folders<R: Runtime>(
+    handle: &AppHandle<R>,
+    os_folders: Vec<OsFolder>,
     mut user: Option<User>,
 ) -> Result<(), DatabaseError> {
     let app_data_dir = handle.path().app_data_dir()?;
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().open(&DBMODELS, db_path)?;
+    let db = handle.state::<Database>();
 
     let rwtx = db.rw_transaction()?;
 
@@ -630,6 +1589,7 @@ pub fn delete_os_folders(
                 }
             }
 
+            remove_bookmarks(&rwtx, &vid.path)?;
             rwtx.remove(vid)?;
         }
 
@@ -656,13 +1616,12 @@ pub fn delete_os_folders(
     Ok(())
 }
 
-pub fn delete_os_videos(
-    handle: &AppHandle,
+pub fn delete_os_videos<R: Runtime>(
+    handle: &AppHandle<R>,
     os_videos: Vec<OsVideo>,
     mut user: Option<User>,
 ) -> Result<(), DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().open(&DBMODELS, db_path)?;
+    let db = handle.state::<Database>();
 
     let rwtx = db.rw_transaction()?;
 
@@ -683,6 +1642,7 @@ pub fn delete_os_videos(
             }
 
             vid._delete_cover_img()?;
+            remove_bookmarks(&rwtx, &vid.path)?;
             rwtx.remove(vid)?;
         }
 
@@ -694,6 +1654,7 @@ pub fn delete_os_videos(
             }
         }
 
+        remove_bookmarks(&rwtx, &vid.path)?;
         rwtx.remove(vid)?;
     }
 
@@ -708,8 +1669,7 @@ pub fn delete_os_videos(
 
 #[command]
 pub fn create_default_user(handle: AppHandle) -> Result<User, DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().create(&DBMODELS, db_path)?;
+    let db = handle.state::<Database>();
 
     let rtx = db.rw_transaction()?;
     let user = User {
@@ -726,8 +1686,7 @@ pub fn create_default_user(handle: AppHandle) -> Result<User, DatabaseError> {
 
 #[command]
 pub fn get_default_user(handle: AppHandle) -> Result<User, DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().create(&DBMODELS, db_path)?;
+    let db = handle.state::<Database>();
 
     let rtx = db.r_transaction()?;
     let user: Option<User> = rtx.get().primary("1")?;
@@ -736,9 +1695,11 @@ pub fn get_default_user(handle: AppHandle) -> Result<User, DatabaseError> {
 }
 
 #[command]
-pub fn get_user_by_id(handle: AppHandle, user_id: String) -> Result<User, DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().create(&DBMODELS, db_path)?;
+pub fn get_user_by_id<R: Runtime>(
+    handle: AppHandle<R>,
+    user_id: String,
+) -> Result<User, DatabaseError> {
+    let db = handle.state::<Database>();
 
     let rtx = db.r_transaction()?;
     let user: Option<User> = rtx.get().primary(user_id.as_str())?;
@@ -748,8 +1709,14 @@ pub fn get_user_by_id(handle: AppHandle, user_id: String) -> Result<User, Databa
 
 #[command]
 pub fn update_user(user: User, handle: AppHandle) -> Result<(), DatabaseError> {
-    let db_path = handle.state::<PathBuf>().to_string_lossy().to_string();
-    let db = Builder::new().open(&DBMODELS, db_path)?;
+    user.settings.mpv_settings.validate()?;
+    save_user(&handle, user)
+}
+
+/// upserts the user without validating its settings,
+/// for internal updates such as the last watched video.
+pub fn save_user<R: Runtime>(handle: &AppHandle<R>, user: User) -> Result<(), DatabaseError> {
+    let db = handle.state::<Database>();
 
     let rtx = db.rw_transaction()?;
     rtx.upsert(user)?;
@@ -757,3 +1724,569 @@ pub fn update_user(user: User, handle: AppHandle) -> Result<(), DatabaseError> {
 
     Ok(())
 }
+
+#[command]
+pub fn get_mpv_plugins(handle: AppHandle) -> Result<Vec<MpvPlugin>, DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rtx = db.r_transaction()?;
+    let plugins: Vec<MpvPlugin> = rtx.scan().primary()?.all()?.try_collect()?;
+
+    Ok(plugins)
+}
+
+pub fn get_mpv_plugin(handle: &AppHandle, name: &str) -> Result<Option<MpvPlugin>, DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rtx = db.r_transaction()?;
+    let plugin: Option<MpvPlugin> = rtx.get().primary(name)?;
+
+    Ok(plugin)
+}
+
+pub fn update_mpv_plugins(
+    handle: &AppHandle,
+    plugins: Vec<MpvPlugin>,
+) -> Result<(), DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rwtx = db.rw_transaction()?;
+    for plugin in plugins {
+        rwtx.upsert(plugin)?;
+    }
+    rwtx.commit()?;
+
+    Ok(())
+}
+
+pub fn delete_mpv_plugin(handle: &AppHandle, plugin: MpvPlugin) -> Result<(), DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rwtx = db.rw_transaction()?;
+    rwtx.remove(plugin)?;
+    rwtx.commit()?;
+
+    Ok(())
+}
+
+/// every bookmark in the video, ordered by timestamp
+#[command]
+pub fn get_bookmarks<R: Runtime>(
+    handle: AppHandle<R>,
+    video_path: String,
+) -> Result<Vec<Bookmark>, DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rtx = db.r_transaction()?;
+    let mut bookmarks: Vec<Bookmark> = rtx
+        .scan()
+        .secondary(BookmarkKey::video_path)?
+        .start_with(video_path.as_str())?
+        .take_while(|e: &Result<Bookmark, db_type::Error>| match e {
+            Ok(bookmark) => bookmark.video_path == video_path,
+            Err(_) => false,
+        })
+        .try_collect()?;
+    bookmarks.sort_by_key(|bookmark| bookmark.timestamp);
+
+    Ok(bookmarks)
+}
+
+/// bookmarks are keyed by their video's path,
+/// so they're removed along with the video instead of outliving it.
+fn remove_bookmarks(
+    rwtx: &transaction::RwTransaction,
+    video_path: &str,
+) -> Result<(), DatabaseError> {
+    let bookmarks: Vec<Bookmark> = rwtx
+        .scan()
+        .secondary(BookmarkKey::video_path)?
+        .start_with(video_path)?
+        .take_while(|e: &Result<Bookmark, db_type::Error>| match e {
+            Ok(bookmark) => bookmark.video_path == video_path,
+            Err(_) => false,
+        })
+        .try_collect()?;
+
+    for bookmark in bookmarks {
+        rwtx.remove(bookmark)?;
+    }
+
+    Ok(())
+}
+
+/// the `name` defaults to the formatted timestamp
+#[command]
+pub fn add_bookmark<R: Runtime>(
+    handle: AppHandle<R>,
+    video_path: String,
+    timestamp: u64,
+    name: Option<String>,
+    note: Option<String>,
+) -> Result<Bookmark, DatabaseError> {
+    let (create_date, create_time) = get_date_time();
+    let bookmark = Bookmark {
+        video_path,
+        timestamp,
+        name: name.unwrap_or_else(|| format_timestamp(timestamp)),
+        note: note.unwrap_or_default(),
+        create_date,
+        create_time,
+    };
+    update_bookmark(handle, bookmark.clone())?;
+
+    Ok(bookmark)
+}
+
+/// the timestamp is part of the key,
+/// so moving a bookmark means deleting & re-adding it
+#[command]
+pub fn update_bookmark<R: Runtime>(
+    handle: AppHandle<R>,
+    bookmark: Bookmark,
+) -> Result<(), DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rwtx = db.rw_transaction()?;
+    rwtx.upsert(bookmark)?;
+    rwtx.commit()?;
+
+    Ok(())
+}
+
+#[command]
+pub fn delete_bookmark(handle: AppHandle, bookmark: Bookmark) -> Result<(), DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rwtx = db.rw_transaction()?;
+    rwtx.remove(bookmark)?;
+    rwtx.commit()?;
+
+    Ok(())
+}
+
+/// an empty queue when the user never queued anything
+pub fn get_queue<R: Runtime>(handle: &AppHandle<R>, user_id: &str) -> Result<Queue, DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rtx = db.r_transaction()?;
+    let queue: Option<Queue> = rtx.get().primary(user_id)?;
+
+    Ok(queue.unwrap_or_else(|| {
+        let (update_date, update_time) = get_date_time();
+        Queue {
+            user_id: user_id.to_string(),
+            video_paths: Vec::new(),
+            update_date,
+            update_time,
+        }
+    }))
+}
+
+pub fn save_queue<R: Runtime>(
+    handle: &AppHandle<R>,
+    mut queue: Queue,
+) -> Result<(), DatabaseError> {
+    let db = handle.state::<Database>();
+    (queue.update_date, queue.update_time) = get_date_time();
+
+    let rwtx = db.rw_transaction()?;
+    rwtx.upsert(queue)?;
+    rwtx.commit()?;
+
+    Ok(())
+}
+
+/// looks up every path in order, skipping videos that no longer exist
+pub fn get_os_videos_by_paths<R: Runtime>(
+    handle: &AppHandle<R>,
+    paths: &[String],
+) -> Result<Vec<OsVideo>, DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rtx = db.r_transaction()?;
+    let mut videos = Vec::with_capacity(paths.len());
+    for path in paths {
+        let vid: Option<OsVideo> = rtx.get().primary(path.as_str())?;
+        videos.extend(vid);
+    }
+
+    Ok(videos)
+}
+
+/// the videos with any of these fingerprints, wherever they are in the library
+pub fn get_os_videos_by_fingerprints<R: Runtime>(
+    handle: &AppHandle<R>,
+    fingerprints: &[&str],
+) -> Result<Vec<OsVideo>, DatabaseError> {
+    let db = handle.state::<Database>();
+
+    let rtx = db.r_transaction()?;
+    let mut videos = Vec::new();
+    for fingerprint in fingerprints {
+        let matches: Vec<OsVideo> = rtx
+            .scan()
+            .secondary(OsVideoKey::fingerprint_key)?
+            .start_with(*fingerprint)?
+            .take_while(|e: &Result<OsVideo, db_type::Error>| match e {
+                Ok(vid) => vid.fingerprint() == Some(*fingerprint),
+                Err(_) => false,
+            })
+            .try_collect()?;
+        videos.extend(matches);
+    }
+
+    Ok(videos)
+}
+
+/// every video in the folder & all of its child folders
+pub fn get_os_videos_in_tree<R: Runtime>(
+    handle: &AppHandle<R>,
+    folder_path: &str,
+) -> Result<Vec<OsVideo>, DatabaseError> {
+    let db = handle.state::<Database>();
+    let rtx = db.r_transaction()?;
+
+    let mut videos = Vec::new();
+    let mut folder_paths = vec![folder_path.to_string()];
+    while let Some(path) = folder_paths.pop() {
+        let child_folders: Vec<OsFolder> = rtx
+            .scan()
+            .secondary(OsFolderKey::parent_path)?
+            .start_with(Some(path.as_str()))?
+            .try_collect()?;
+        folder_paths.extend(
+            child_folders
+                .into_iter()
+                .filter(|folder| folder.parent_path.as_deref() == Some(path.as_str()))
+                .map(|folder| folder.path),
+        );
+
+        let folder_videos: Vec<OsVideo> = rtx
+            .scan()
+            .secondary(OsVideoKey::main_folder_path)?
+            .start_with(path.as_str())?
+            .take_while(|e: &Result<OsVideo, db_type::Error>| match e {
+                Ok(vid) => vid.main_folder_path == path,
+                Err(_) => false,
+            })
+            .try_collect()?;
+        videos.extend(folder_videos);
+    }
+
+    Ok(videos)
+}
+
+/// the folders added to the library, of every user
+pub fn get_root_os_folders(handle: &AppHandle) -> Result<Vec<OsFolder>, DatabaseError> {
+    let db = handle.state::<Database>();
+    let rtx = db.r_transaction()?;
+
+    let mut folders: Vec<OsFolder> = rtx.scan().primary()?.all()?.try_collect()?;
+    folders.retain(|folder| folder.parent_path.is_none());
+
+    Ok(folders)
+}
+
+/// the direct child folders & videos of a folder,
+/// as `upsert_read_os_dir` expects them to diff against.
+pub fn get_os_folder_entries(
+    handle: &AppHandle,
+    folder_path: &str,
+) -> Result<(Vec<OsFolder>, Vec<OsVideo>), DatabaseError> {
+    let db = handle.state::<Database>();
+    let rtx = db.r_transaction()?;
+
+    let mut child_folders: Vec<OsFolder> = rtx
+        .scan()
+        .secondary(OsFolderKey::parent_path)?
+        .start_with(Some(folder_path))?
+        .try_collect()?;
+    child_folders.retain(|folder| folder.parent_path.as_deref() == Some(folder_path));
+
+    let videos: Vec<OsVideo> = rtx
+        .scan()
+        .secondary(OsVideoKey::main_folder_path)?
+        .start_with(folder_path)?
+        .take_while(|e: &Result<OsVideo, db_type::Error>| match e {
+            Ok(vid) => vid.main_folder_path == folder_path,
+            Err(_) => false,
+        })
+        .try_collect()?;
+
+    Ok((child_folders, videos))
+}
+
+/// fingerprints the videos scanned before fingerprints existed,
+/// so they can still be recognised once they're moved.
+/// the ones done before the scan was cancelled are still saved.
+pub fn backfill_fingerprints(
+    handle: &AppHandle,
+    os_videos: &[OsVideo],
+    scan: &Scan,
+) -> Result<(), DatabaseError> {
+    let missing: Vec<&OsVideo> = os_videos
+        .iter()
+        .filter(|vid| vid.metadata.is_some() && vid.fingerprint().is_none())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    scan.fingerprints_started(missing.len());
+    let fingerprints: Vec<(&str, String)> = missing
+        .par_iter()
+        .filter_map(|vid| {
+            if scan.is_cancelled() {
+                return None;
+            }
+            let fingerprint = FileMetadata::fingerprint(&vid.path).ok();
+            scan.fingerprint_done();
+            Some((vid.path.as_str(), fingerprint?))
+        })
+        .collect();
+    if fingerprints.is_empty() {
+        return Ok(());
+    }
+
+    let db = handle.state::<Database>();
+    let rwtx = db.rw_transaction()?;
+
+    for (path, fingerprint) in fingerprints {
+        // the one passed in may be older than the stored one
+        let vid: Option<OsVideo> = rwtx.get().primary(path)?;
+        let Some(mut vid) = vid else {
+            continue;
+        };
+        if let Some(metadata) = vid.metadata.as_mut() {
+            metadata.fingerprint = Some(fingerprint);
+            rwtx.upsert(vid)?;
+        }
+    }
+
+    rwtx.commit()?;
+
+    Ok(())
+}
+
+/// moves the bookmarks of moved videos to their new paths.
+/// deleting the old videos takes their bookmarks with them, so this runs first.
+pub fn migrate_moved_bookmarks<R: Runtime>(
+    handle: &AppHandle<R>,
+    moves: &[(String, OsVideo)],
+) -> Result<(), DatabaseError> {
+    if moves.is_empty() {
+        return Ok(());
+    }
+
+    let db = handle.state::<Database>();
+    let rwtx = db.rw_transaction()?;
+
+    for (old_path, vid) in moves {
+        let bookmarks: Vec<Bookmark> = rwtx
+            .scan()
+            .secondary(BookmarkKey::video_path)?
+            .start_with(old_path.as_str())?
+            .take_while(|e: &Result<Bookmark, db_type::Error>| match e {
+                Ok(bookmark) => bookmark.video_path == *old_path,
+                Err(_) => false,
+            })
+            .try_collect()?;
+        for bookmark in bookmarks {
+            // the path is part of the primary key
+            rwtx.remove(bookmark.clone())?;
+            rwtx.insert(Bookmark {
+                video_path: vid.path.clone(),
+                ..bookmark
+            })?;
+        }
+    }
+
+    rwtx.commit()?;
+
+    Ok(())
+}
+
+/// points everything else that referenced the old paths of moved videos at the new ones.
+/// the deletion may have already cleared `user.last_watched_video` in the database,
+/// so the user is passed in as it was before the scan.
+pub fn migrate_moved_videos<R: Runtime>(
+    handle: &AppHandle<R>,
+    moves: &[(String, OsVideo)],
+    mut user: User,
+) -> Result<(), DatabaseError> {
+    if moves.is_empty() {
+        return Ok(());
+    }
+
+    let db = handle.state::<Database>();
+    let rwtx = db.rw_transaction()?;
+
+    let new_video = |path: &str| {
+        moves
+            .iter()
+            .find(|(old_path, _)| old_path == path)
+            .map(|(_, vid)| vid)
+    };
+
+    for (old_path, _) in moves {
+        // still there when it was moved out of a folder that wasn't rescanned yet
+        let old: Option<OsVideo> = rwtx.get().primary(old_path.as_str())?;
+        if let Some(old) = old {
+            old._delete_cover_img()?;
+            rwtx.remove(old)?;
+        }
+    }
+
+    let queues: Vec<Queue> = rwtx.scan().primary()?.all()?.try_collect()?;
+    for mut queue in queues {
+        let mut changed = false;
+        for path in queue.video_paths.iter_mut() {
+            if let Some(vid) = new_video(path) {
+                *path = vid.path.clone();
+                changed = true;
+            }
+        }
+        if changed {
+            rwtx.upsert(queue)?;
+        }
+    }
+
+    let folders: Vec<OsFolder> = rwtx.scan().primary()?.all()?.try_collect()?;
+    for mut folder in folders {
+        let moved = folder
+            .last_watched_video
+            .as_ref()
+            .and_then(|lwv| new_video(&lwv.path));
+        if let Some(vid) = moved {
+            folder.last_watched_video = Some(vid.clone());
+            rwtx.upsert(folder)?;
+        }
+    }
+
+    let moved = user
+        .last_watched_video
+        .as_ref()
+        .and_then(|lwv| new_video(&lwv.path));
+    if let Some(vid) = moved {
+        user.last_watched_video = Some(vid.clone());
+        rwtx.upsert(user)?;
+    }
+
+    rwtx.commit()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::remove_file;
+
+    use super::*;
+
+    fn v1_video(path: &str, watched: bool, position: u64) -> data::v1::OsVideo {
+        data::v1::OsVideo {
+            user_id: "user".to_string(),
+            main_folder_path: "/shows".to_string(),
+            path: path.to_string(),
+            title: path.to_string(),
+            cover_img_path: None,
+            watched,
+            duration: 1440,
+            position,
+            metadata: Some(data::v1::FileMetadata {
+                created: None,
+                modified: None,
+                accessed: None,
+                size: Some(1024),
+            }),
+            update_date: "2024-01-01".to_string(),
+            update_time: "09:30pm".to_string(),
+        }
+    }
+
+    #[test]
+    fn migrates_a_database_from_the_last_release() {
+        let db_path = env::temp_dir().join(format!("mpv-shelf-migrate-{}.rdb", std::process::id()));
+        let _ = remove_file(&db_path);
+
+        let mut v1_models = Models::new();
+        v1_models.define::<data::v1::User>().unwrap();
+        v1_models.define::<data::v1::OsFolder>().unwrap();
+        v1_models.define::<data::v1::OsVideo>().unwrap();
+        {
+            let db = Builder::new().create(&v1_models, &db_path).unwrap();
+            let rwtx = db.rw_transaction().unwrap();
+            rwtx.insert(data::v1::User {
+                id: "user".to_string(),
+                username: "user".to_string(),
+                settings: data::v1::Settings {
+                    user_id: "user".to_string(),
+                    mpv_settings: data::v1::MpvSettings {
+                        exe_path: Some("/usr/bin/mpv".to_string()),
+                        config_path: None,
+                        plugins_path: None,
+                        autoplay: true,
+                    },
+                    update_date: "2024-01-01".to_string(),
+                    update_time: "09:30pm".to_string(),
+                },
+                last_watched_video: Some(v1_video("/shows/1.mkv", false, 300)),
+            })
+            .unwrap();
+            rwtx.insert(data::v1::OsFolder {
+                user_id: "user".to_string(),
+                path: "/shows".to_string(),
+                title: "shows".to_string(),
+                parent_path: None,
+                last_watched_video: Some(v1_video("/shows/1.mkv", false, 300)),
+                cover_img_path: None,
+                update_date: "2024-01-01".to_string(),
+                update_time: "09:30pm".to_string(),
+            })
+            .unwrap();
+            rwtx.insert(v1_video("/shows/1.mkv", false, 300)).unwrap();
+            rwtx.insert(v1_video("/shows/2.mkv", true, 1440)).unwrap();
+            rwtx.commit().unwrap();
+        }
+
+        let db = Builder::new().create(&DBMODELS, &db_path).unwrap();
+        migrate_database(&db).unwrap();
+
+        let rtx = db.r_transaction().unwrap();
+        let user: User = rtx.get().primary("user").unwrap().unwrap();
+        assert_eq!(
+            user.settings.mpv_settings.exe_path.as_deref(),
+            Some("/usr/bin/mpv")
+        );
+        assert!(user.settings.mpv_settings.autoplay);
+        assert_eq!(
+            user.settings.ignore_patterns,
+            data::v2::default_ignore_patterns()
+        );
+        assert!(user.last_watched_video.is_some_and(|vid| vid.in_progress));
+
+        let folder: OsFolder = rtx.get().primary("/shows").unwrap().unwrap();
+        assert!(!folder.audiobook);
+        assert_eq!(
+            folder.last_watched_video.map(|vid| vid.path).as_deref(),
+            Some("/shows/1.mkv")
+        );
+
+        let in_progress: OsVideo = rtx.get().primary("/shows/1.mkv").unwrap().unwrap();
+        assert!(in_progress.in_progress);
+        assert_eq!(in_progress.position, 300);
+        assert_eq!(
+            in_progress.metadata.and_then(|metadata| metadata.size),
+            Some(1024)
+        );
+
+        let watched: OsVideo = rtx.get().primary("/shows/2.mkv").unwrap().unwrap();
+        assert!(watched.watched && !watched.in_progress);
+
+        drop(rtx);
+        drop(db);
+        let _ = remove_file(&db_path);
+    }
+}
@@ -21,6 +21,8 @@ pub enum MpvShelfError {
     ReadDir(#[from] ReadDirError),
     #[error("{0}")]
     Ffmpeg(#[from] FfmpegError),
+    #[error("{0}")]
+    WatchLater(#[from] WatchLaterError),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -39,6 +41,82 @@ pub enum DatabaseError {
     TuariError(#[from] tauri::Error),
     #[error("{0}")]
     SortType(#[from] SortTypeError),
+    #[error("{0}")]
+    MpvSettings(#[from] MpvSettingsError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MpvSettingsError {
+    #[error("mpv config directory not found: {0}")]
+    ConfigPathNotFound(String),
+    #[error("mpv config path is not a directory: {0}")]
+    ConfigPathNotDir(String),
+    #[error("mpv plugins directory not found: {0}")]
+    PluginsPathNotFound(String),
+    #[error("mpv plugins path is not a directory: {0}")]
+    PluginsPathNotDir(String),
+    #[error("the player command template is empty")]
+    EmptyCommandTemplate,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum QueueError {
+    #[error("{0}")]
+    Database(#[from] DatabaseError),
+    #[error("{0}")]
+    Mpv(#[from] MpvError),
+    #[error("queue index {0} is out of bounds for a queue of {1} videos")]
+    IndexOutOfBounds(usize, usize),
+    #[error("the queue is empty")]
+    Empty,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum WatcherError {
+    #[error("{0}")]
+    Database(#[from] DatabaseError),
+    #[error("{0}")]
+    Notify(#[from] notify::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PluginError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Database(#[from] DatabaseError),
+    #[error("{0:#?}")]
+    Tuari(#[from] tauri::Error),
+    #[error("not an mpv script or script-opts file: {0}")]
+    Unsupported(String),
+    #[error("a plugin named {0} is already installed")]
+    AlreadyInstalled(String),
+    #[error("plugin not found: {0}")]
+    NotFound(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ExportError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Database(#[from] DatabaseError),
+    #[error("{0:#?}")]
+    Tuari(#[from] tauri::Error),
+    #[error("failed to write the plugin manifest: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("{0}")]
+    Zip(#[from] zip::result::ZipError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum WatchLaterError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Database(#[from] DatabaseError),
+    #[error("{0:#?}")]
+    Tuari(#[from] tauri::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -49,12 +127,28 @@ pub enum ReadDirError {
     FullyHydrated(String),
     #[error("{0}")]
     Tuari(#[from] tauri::Error),
+    #[error("scan {0} was cancelled")]
+    Cancelled(String),
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum MpvError {
     #[error("Mpv Player was not found at: {0}")]
     AbsolutePathNotFound(String),
+    #[error("Mpv Player {0} is too old, mpvshelf needs at least {1}")]
+    VersionTooOld(String, String),
+    #[error("Mpv Player couldn't open: {0}")]
+    FileNotFound(String),
+    #[error("Mpv Player has no decoder for codec: {0}")]
+    UnsupportedCodec(String),
+    #[error("Mpv Player rejected its config: {0}")]
+    ConfigError(String),
+    #[error("Mpv Player was killed by signal {0}")]
+    KilledBySignal(i32),
+    #[error("Mpv Player quit & saved its position to watch_later")]
+    QuitWatchLater,
+    #[error("Mpv Player exited with code {0}: {1}")]
+    ExitCode(i32, String),
     #[error("Failed to execute Mpv Player: {0}")]
     IoError(#[from] io::Error),
     #[error("{0:#?}")]
@@ -69,6 +163,20 @@ pub enum MpvError {
     DatabaseError(#[from] DatabaseError),
     #[error("{0}")]
     StdOutError(#[from] MpvStdoutError),
+    #[error("{0}")]
+    Ipc(#[from] MpvIpcError),
+    #[error("Mpv Player is already playing, stop it before playing: {0}")]
+    PlaybackSessionRunning(String),
+    #[error("Mpv Player isn't playing anything.")]
+    NoPlaybackSession,
+    #[error("Mpv Player is still starting, try again in a moment.")]
+    PlaybackSessionStarting,
+    #[error("{0}")]
+    SortType(#[from] SortTypeError),
+    #[error("{0}")]
+    Settings(#[from] MpvSettingsError),
+    #[error("{0}")]
+    Plugin(#[from] PluginError),
 }
 
 #[non_exhaustive]
@@ -88,16 +196,22 @@ pub enum FfmpegError {
 pub enum MpvStdoutError {
     #[error("Failed to convert stdout bytes to String: {0}")]
     Utf8Error(#[from] FromUtf8Error),
-    #[error("failed to find title from mpv's stdout:\n {0}")]
-    MissingVideoTitle(String),
-    #[error("failed to find timestamp from mpv's stdout:\n {0}")]
-    MissingTimestamp(String),
     #[error("failed to parse int: {0}; reason: {1}")]
     ParseInt(String, String),
     #[error("the given timestamp is invalid: {0}")]
     InvalidTimestamp(String),
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum MpvIpcError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("failed to parse mpv's ipc message: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("timed out connecting to mpv's ipc server at: {0}")]
+    ConnectTimeout(String),
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum HttpClientError {
     #[error("{0}")]
@@ -144,6 +258,30 @@ impl From<PathBuf> for MpvError {
     }
 }
 
+impl From<QueueError> for InvokeError {
+    fn from(error: QueueError) -> Self {
+        InvokeError::from_error(error)
+    }
+}
+
+impl From<PluginError> for InvokeError {
+    fn from(error: PluginError) -> Self {
+        InvokeError::from_error(error)
+    }
+}
+
+impl From<ExportError> for InvokeError {
+    fn from(error: ExportError) -> Self {
+        InvokeError::from_error(error)
+    }
+}
+
+impl From<WatchLaterError> for InvokeError {
+    fn from(error: WatchLaterError) -> Self {
+        InvokeError::from_error(error)
+    }
+}
+
 impl From<MpvError> for InvokeError {
     fn from(error: MpvError) -> Self {
         InvokeError::from_error(error)
@@ -1,6 +1,5 @@
 use std::collections::HashSet;
 use std::io::Write;
-use std::os::windows::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 //use std::time::Instant;
@@ -16,15 +15,25 @@ use std::{fs::read_dir, process::Command};
 use tauri_plugin_shell::process::CommandEvent;
 use tokio::io::AsyncWriteExt;
 
-use crate::database::data::v1::{OsVideo, User};
-use crate::database::{data::v1::OsFolder, update_os_folders};
-use crate::database::{delete_os_folders, delete_os_videos, update_os_videos, HasPath, SortType};
+use crate::database::data::v2::{Chapter, OsVideo, Subtitle, User};
+use crate::database::{
+    backfill_fingerprints, delete_os_videos, get_os_folder_by_path, get_os_folder_entries,
+    get_os_videos_by_fingerprints, get_os_videos_by_paths, get_os_videos_in_tree,
+    migrate_moved_bookmarks, migrate_moved_videos, preserve_os_folder_state,
+    preserve_os_video_state, remove_os_folders, update_os_videos, HasPath, SortType,
+};
+use crate::database::{data::v2::OsFolder, update_os_folders};
 use crate::error::{DatabaseError, FfmpegError, MpvError, MpvShelfError, ReadDirError};
+use crate::library_ignore::LibraryIgnore;
 use crate::misc::get_date_time;
 use crate::mpv::{MpvPlaybackData, EPISODE_TITLE_REGEX};
+use crate::scan::Scan;
+use crate::watch_later::merge_scanned_watch_later;
+use crate::watcher::watch_library;
 use rayon::iter::ParallelIterator;
 use reqwest::Client;
-use tauri::{command, AppHandle, Emitter, Manager};
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle, Emitter, Manager, Runtime};
 use tauri_plugin_shell::ShellExt;
 
 use crate::error::HttpClientError;
@@ -63,44 +72,238 @@ impl Pushable for HashSet<String> {
     }
 }
 
+fn is_audio_file(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|extension| {
+        SUPPORTED_AUDIO_FORMATS.contains(&*extension.to_string_lossy().to_lowercase())
+    })
+}
+
+/// subtitle folders are scanned into their videos' subtitles,
+/// instead of being treated as child folders.
+fn is_subtitle_dir(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy().to_lowercase();
+        name == "subs" || name == "subtitles"
+    })
+}
+
+fn read_subtitle_dir(
+    path: &Path,
+    subtitle_file_paths: &mut impl Pushable,
+) -> Result<(), io::Error> {
+    for entry in read_dir(path)? {
+        let entry_path = entry?.path();
+
+        // releases often nest subs per episode: `Subs/<video name>/English.srt`
+        if entry_path.is_dir() {
+            read_subtitle_dir(&entry_path, subtitle_file_paths)?;
+        } else if let Some(extension) = entry_path.extension() {
+            if SUPPORTED_SUBTITLE_FORMATS.contains(&*extension.to_string_lossy()) {
+                subtitle_file_paths.push(entry_path.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn read_dir_helper(
     path: &str,
+    ignore: &LibraryIgnore,
     child_folder_paths: &mut impl Pushable,
     video_file_paths: &mut impl Pushable,
+    subtitle_file_paths: &mut impl Pushable,
 ) -> Result<(), io::Error> {
     for entry in read_dir(path)? {
         let entry = entry?;
         let entry_path = entry.path();
+        // checked before the extension, folders like `Season 1.5` have one too
+        let is_dir = entry_path.is_dir();
+        if ignore.is_ignored(&entry_path, is_dir) {
+            continue;
+        }
 
-        if let Some(extension) = entry_path.extension() {
+        if is_dir {
+            if is_subtitle_dir(&entry_path) {
+                read_subtitle_dir(&entry_path, subtitle_file_paths)?;
+            } else {
+                child_folder_paths.push(entry_path.to_string_lossy().to_string());
+            }
+        } else if let Some(extension) = entry_path.extension() {
             let extension_lossy = extension.to_string_lossy();
             if SUPPORTED_VIDEO_FORMATS.get_key(&extension_lossy).is_some()
                 || SUPPORTED_AUDIO_FORMATS.get_key(&extension_lossy).is_some()
             {
                 video_file_paths.push(entry_path.to_string_lossy().to_string());
+            } else if SUPPORTED_SUBTITLE_FORMATS.contains(&extension_lossy) {
+                subtitle_file_paths.push(entry_path.to_string_lossy().to_string());
             }
-        } else if entry_path.is_dir() {
-            child_folder_paths.push(entry_path.to_string_lossy().to_string());
-            continue;
         }
     }
 
     Ok(())
 }
 
+/// matches subtitles to the video by filename stem, eg:
+/// `<video>.srt`, `<video>.ja.srt`, `<video>.en.ass` or `Subs/<video>/English.srt`
+pub fn match_subtitles(video_path: &str, subtitle_paths: &[String]) -> Vec<Subtitle> {
+    let Some(video_stem) = Path::new(video_path).file_stem() else {
+        return Vec::new();
+    };
+    let video_stem = video_stem.to_string_lossy();
+
+    let mut subtitles: Vec<Subtitle> = subtitle_paths
+        .iter()
+        .filter_map(|sub_path| {
+            let path = Path::new(sub_path);
+            let sub_stem = path.file_stem()?.to_string_lossy();
+            let in_video_dir = path
+                .parent()
+                .and_then(|parent| parent.file_name())
+                .is_some_and(|name| name.to_string_lossy() == video_stem);
+
+            let lang = if sub_stem == video_stem {
+                None
+            } else if let Some(lang) = sub_stem
+                .strip_prefix(video_stem.as_ref())
+                .and_then(|rest| rest.strip_prefix('.'))
+            {
+                Some(lang.to_string())
+            } else if in_video_dir {
+                Some(sub_stem.to_string())
+            } else {
+                return None;
+            };
+
+            Some(Subtitle {
+                path: sub_path.clone(),
+                lang,
+            })
+        })
+        .collect();
+
+    // mpv loads a vobsub's `.sub` through its `.idx`
+    let idx_paths: HashSet<PathBuf> = subtitles
+        .iter()
+        .map(|sub| Path::new(&sub.path))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "idx"))
+        .map(|path| path.with_extension(""))
+        .collect();
+    subtitles.retain(|sub| {
+        let path = Path::new(&sub.path);
+        !(path.extension().is_some_and(|ext| ext == "sub")
+            && idx_paths.contains(&path.with_extension("")))
+    });
+    subtitles.sort_by(|a, b| a.path.cmp(&b.path));
+
+    subtitles
+}
+
 type FolderGroup = (OsFolder, Vec<OsFolder>, Vec<OsVideo>);
 
-fn delete_stale_entries(
-    handle: AppHandle,
+fn delete_stale_entries<R: Runtime>(
+    handle: &AppHandle<R>,
     old_dirs: Vec<OsFolder>,
     old_vids: Vec<OsVideo>,
     user: User,
 ) -> Result<(), DatabaseError> {
-    delete_os_folders(handle.clone(), old_dirs, Some(user.clone()))?;
-    delete_os_videos(&handle, old_vids, Some(user))?;
+    remove_os_folders(handle, old_dirs, Some(user.clone()))?;
+    delete_os_videos(handle, old_vids, Some(user))?;
     Ok(())
 }
 
+/// pairs the scanned videos with the deleted ones they were moved from,
+/// then deletes the stale entries.
+/// deleting a video takes its bookmarks with it, so the moved ones are re-keyed first.
+/// returns the moves as `(old path, new video)`.
+fn replace_stale_entries<R: Runtime>(
+    handle: &AppHandle<R>,
+    user: &User,
+    deleted_entries: Option<(Vec<OsFolder>, Vec<OsVideo>)>,
+    videos: &mut [OsVideo],
+) -> Result<Vec<(String, OsVideo)>, DatabaseError> {
+    let mut moved_candidates = Vec::new();
+    if let Some((deleted_dirs, deleted_videos)) = &deleted_entries {
+        // read before they're deleted, in case they were only moved
+        moved_candidates = get_moved_candidates(handle, deleted_dirs, deleted_videos)?;
+    }
+    // refreshed videos are read again from scratch
+    preserve_os_video_state(handle, videos)?;
+    let moves = pair_moved_videos(handle, &user.id, moved_candidates, videos)?;
+
+    migrate_moved_bookmarks(handle, &moves)?;
+    if let Some((deleted_dirs, deleted_videos)) = deleted_entries {
+        delete_stale_entries(handle, deleted_dirs, deleted_videos, user.clone())?;
+    }
+
+    Ok(moves)
+}
+
+/// the videos a scan is about to delete,
+/// including the ones in deleted folders.
+fn get_moved_candidates<R: Runtime>(
+    handle: &AppHandle<R>,
+    deleted_dirs: &[OsFolder],
+    deleted_videos: &[OsVideo],
+) -> Result<Vec<OsVideo>, DatabaseError> {
+    let paths: Vec<String> = deleted_videos.iter().map(|vid| vid.path.clone()).collect();
+    let mut candidates = get_os_videos_by_paths(handle, &paths)?;
+    for dir in deleted_dirs {
+        candidates.extend(get_os_videos_in_tree(handle, &dir.path)?);
+    }
+    Ok(candidates)
+}
+
+/// pairs the added videos with deleted ones by fingerprint,
+/// & carries the old state over. returns the `(old path, new video)` pairs.
+///
+/// a video moved out of a folder that wasn't rescanned yet is still in the database,
+/// so the user's other missing videos are paired too.
+fn pair_moved_videos<R: Runtime>(
+    handle: &AppHandle<R>,
+    user_id: &str,
+    candidates: Vec<OsVideo>,
+    videos: &mut [OsVideo],
+) -> Result<Vec<(String, OsVideo)>, DatabaseError> {
+    let new_paths: HashSet<String> = videos.iter().map(|vid| vid.path.clone()).collect();
+    let mut by_fingerprint: HashMap<String, OsVideo> = HashMap::new();
+    let add_candidates = |by_fingerprint: &mut HashMap<String, OsVideo>, vids: Vec<OsVideo>| {
+        for vid in vids {
+            if let Some(fingerprint) = vid.fingerprint() {
+                if !new_paths.contains(&vid.path) {
+                    by_fingerprint.entry(fingerprint.to_string()).or_insert(vid);
+                }
+            }
+        }
+    };
+    add_candidates(&mut by_fingerprint, candidates);
+
+    // moved in from a folder outside of this scan
+    let unpaired: Vec<&str> = videos
+        .iter()
+        .filter_map(OsVideo::fingerprint)
+        .filter(|fingerprint| !by_fingerprint.contains_key(*fingerprint))
+        .collect();
+    if !unpaired.is_empty() {
+        let mut missing = get_os_videos_by_fingerprints(handle, &unpaired)?;
+        missing.retain(|vid| vid.user_id == user_id && !path_exists(&vid.path));
+        add_candidates(&mut by_fingerprint, missing);
+    }
+
+    let mut moves = Vec::new();
+    for vid in videos.iter_mut() {
+        let old = vid
+            .fingerprint()
+            .and_then(|fingerprint| by_fingerprint.remove(fingerprint));
+        if let Some(old) = old {
+            vid.carry_over(&old);
+            moves.push((old.path, vid.clone()));
+        }
+    }
+
+    Ok(moves)
+}
+
 fn normalize_path_to_unix(path: impl AsRef<str>) -> String {
     path.as_ref().to_lowercase().replace('\\', "/") // Normalize case and separators.
 }
@@ -151,6 +354,7 @@ where
 fn find_stale_metadata(
     old: &[OsVideo],
     new: &HashSet<String>, // new is directly a HashSet<String>
+    subtitle_paths: &[String],
 ) -> Option<HashSet<String>> {
     // Build a map of old videos for easy lookup by path.
     let old_map: HashMap<&str, &OsVideo> = old.iter().map(|p| (p.path.as_str(), p)).collect();
@@ -162,8 +366,11 @@ fn find_stale_metadata(
     new.iter().for_each(|new_path| {
         // Check if the video exists in the old set.
         if let Some(old_video) = old_map.get(new_path.as_str()) {
-            // Check if the video's metadata is stale.
-            if old_video.is_stale_metadata() {
+            // Check if the video's metadata is stale,
+            // or a subtitle was added, renamed or removed next to it.
+            if old_video.is_stale_metadata()
+                || old_video.subtitles != match_subtitles(new_path, subtitle_paths)
+            {
                 result.insert(new_path.clone()); // Add to result if metadata is stale.
             }
         } else {
@@ -189,13 +396,21 @@ fn find_stale_metadata(
 
 fn find_stale_entries(
     main_dir: &str,
+    ignore: &LibraryIgnore,
     old_dirs: Option<&mut Vec<OsFolder>>,
     old_videos: Option<&mut Vec<OsVideo>>,
 ) -> Result<StaleEntries, ReadDirError> {
     // Collect new directories and videos from the filesystem.
     let mut new_dirs = HashSet::new();
     let mut new_videos = HashSet::new();
-    read_dir_helper(main_dir, &mut new_dirs, &mut new_videos)?;
+    let mut new_subtitles = Vec::new();
+    read_dir_helper(
+        main_dir,
+        ignore,
+        &mut new_dirs,
+        &mut new_videos,
+        &mut new_subtitles,
+    )?;
 
     // If both old_dirs and old_videos are None, this is a fresh scan (no previous entries).
     if old_dirs.is_none() && old_videos.is_none() {
@@ -212,11 +427,11 @@ fn find_stale_entries(
         None => &mut Vec::new(),
     };
 
-    // Filter out missing videos (deleted videos)
+    // Filter out missing videos (deleted videos), ignored ones count as deleted too
     let deleted_videos: Vec<OsVideo> = old_videos
         .iter()
         .filter_map(|pan| {
-            if !path_exists(&pan.path) {
+            if !path_exists(&pan.path) || ignore.is_ignored(Path::new(&pan.path), false) {
                 return Some(pan.clone());
             }
             None
@@ -227,7 +442,7 @@ fn find_stale_entries(
     let deleted_dirs: Vec<OsFolder> = old_dirs
         .iter()
         .filter_map(|dir| {
-            if !path_exists(&dir.path) {
+            if !path_exists(&dir.path) || ignore.is_ignored(Path::new(&dir.path), true) {
                 return Some(dir.clone());
             }
             None
@@ -240,7 +455,7 @@ fn find_stale_entries(
 
     // Find missing paths (stale directories) and videos
     let dirs = find_missing_paths(old_dirs, new_dirs.iter());
-    let videos = find_stale_metadata(old_videos, &new_videos);
+    let videos = find_stale_metadata(old_videos, &new_videos, &new_subtitles);
 
     // Return the found stale entries, including deleted items
     match (
@@ -266,10 +481,18 @@ pub async fn upsert_read_os_dir(
     user: User,
     mut old_dirs: Option<Vec<OsFolder>>,
     mut old_videos: Option<Vec<OsVideo>>,
+    scan_id: Option<String>,
 ) -> Result<bool, MpvShelfError> {
     // Find stale entries based on the provided directory and old data.
-    let mut stale_entries = find_stale_entries(&dir, old_dirs.as_mut(), old_videos.as_mut())?;
+    let ignore = LibraryIgnore::new(&user.settings.ignore_patterns, Path::new(&dir));
+    let mut stale_entries =
+        find_stale_entries(&dir, &ignore, old_dirs.as_mut(), old_videos.as_mut())?;
     //println!("stale_entries: {:#?}", stale_entries);
+    let scan = Scan::start(&handle, scan_id.unwrap_or_else(|| dir.clone()));
+    if let Some(old_videos) = &old_videos {
+        backfill_fingerprints(&handle, old_videos, &scan)?;
+        scan.check_cancelled()?;
+    }
 
     // If there are no stale entries and either `old_dirs` or `old_videos` is provided,
     // return `false` to prevent unnecessary re-rendering.
@@ -277,19 +500,38 @@ pub async fn upsert_read_os_dir(
         return Ok(false);
     }
 
+    sync_stale_entries(
+        handle,
+        dir,
+        parent_path,
+        user,
+        stale_entries,
+        &ignore,
+        &scan,
+    )
+    .await?;
+
+    Ok(true)
+}
+
+/// reads the stale entries, then writes them & deletes the deleted ones.
+async fn sync_stale_entries(
+    handle: AppHandle,
+    dir: String,
+    parent_path: Option<String>,
+    user: User,
+    mut stale_entries: StaleEntries,
+    ignore: &LibraryIgnore,
+    scan: &Scan,
+) -> Result<(), MpvShelfError> {
+    let is_root = parent_path.is_none();
+    // deleted only once the scan can't be cancelled anymore
+    let mut deleted_entries = None;
     if let StaleEntries::Found {
         ref mut deleted, ..
     } = stale_entries
     {
-        if let Some(deleted_entries) = deleted.take() {
-            // only move the deleted entries.
-            delete_stale_entries(
-                handle.clone(),
-                deleted_entries.0,
-                deleted_entries.1,
-                user.clone(),
-            )?;
-        }
+        deleted_entries = deleted.take();
     }
     if let StaleEntries::Found { dirs, videos, .. } = &stale_entries {
         if dirs.is_none() && videos.is_none() {
@@ -297,34 +539,235 @@ pub async fn upsert_read_os_dir(
         }
     }
 
-    let (main_folder, mut new_cfs, mut videos) =
-        read_os_folder_dir(&handle, dir, user.id, None, parent_path, stale_entries)?;
+    let (main_folder, mut new_cfs, mut videos) = read_os_folder_dir(
+        &handle,
+        dir,
+        user.id.clone(),
+        None,
+        parent_path,
+        stale_entries,
+        ignore,
+        scan,
+    )?;
+    // child folders swallow their errors, including being cancelled
+    scan.check_cancelled()?;
     new_cfs.push(main_folder);
 
+    scan.thumbnails_started(videos.len());
     futures_util::stream::iter(videos.iter_mut().enumerate())
         .for_each_concurrent(None, |(i, vid)| {
             let handle = handle.clone();
             async move {
+                if scan.is_cancelled() {
+                    return;
+                }
                 if let Some(cip) = vid.cover_img_path.as_ref() {
                     match ffmpeg_extract_frame(&handle, Some(i + 3), &vid.path, Path::new(cip))
                         .await
                     {
-                        Ok(dur) => {
-                            if let Ok(dur) = MpvPlaybackData::get_duration(dur) {
+                        Ok(output) => {
+                            if let Ok(dur) = MpvPlaybackData::get_duration(output.duration) {
                                 vid.duration = dur;
                             }
+                            vid.chapters = output.chapters;
                         }
                         Err(e) => {
                             eprintln!("failed to process {} with ffmpeg {i}: {:?}", vid.path, e)
                         }
                     }
                 }
+                scan.thumbnail_done();
             }
         })
         .await;
+    // the thumbnails made so far are overwritten by the next scan
+    scan.check_cancelled()?;
 
+    let moves = replace_stale_entries(&handle, &user, deleted_entries, &mut videos)?;
+    merge_scanned_watch_later(&handle, &user, &mut videos)?;
     update_os_videos(handle.clone(), videos)?;
-    update_os_folders(handle, new_cfs)?;
+    preserve_os_folder_state(&handle, &mut new_cfs)?;
+    update_os_folders(handle.clone(), new_cfs)?;
+    migrate_moved_videos(&handle, &moves, user)?;
+
+    if is_root {
+        if let Err(e) = watch_library(&handle) {
+            eprintln!("failed to watch the library: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// what `apply_library_diff` would change in the library, by path
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LibraryDiff {
+    pub dir: String,
+    pub parent_path: Option<String>,
+    pub added_folders: Vec<String>,
+    pub added_videos: Vec<String>,
+    /// gone from the disk or ignored,
+    /// along with everything in the removed folders
+    pub removed_folders: Vec<String>,
+    pub removed_videos: Vec<String>,
+    /// changed on disk, so read again
+    pub refreshed_videos: Vec<String>,
+}
+
+impl LibraryDiff {
+    /// `old_dirs` & `old_videos` as `find_stale_entries` left them, without the deleted ones
+    fn new(
+        dir: String,
+        parent_path: Option<String>,
+        stale_entries: &StaleEntries,
+        old_dirs: &[OsFolder],
+        old_videos: &[OsVideo],
+    ) -> Self {
+        let mut diff = Self {
+            dir,
+            parent_path,
+            added_folders: Vec::new(),
+            added_videos: Vec::new(),
+            removed_folders: Vec::new(),
+            removed_videos: Vec::new(),
+            refreshed_videos: Vec::new(),
+        };
+        let StaleEntries::Found {
+            dirs,
+            videos,
+            deleted,
+        } = stale_entries
+        else {
+            return diff;
+        };
+
+        let old_dir_paths: HashSet<&str> = old_dirs.iter().map(HasPath::path).collect();
+        let old_video_paths: HashSet<&str> = old_videos.iter().map(HasPath::path).collect();
+        // old entries that are still on disk are only in the sets when they changed
+        for path in dirs.iter().flatten() {
+            if !old_dir_paths.contains(path.as_str()) {
+                diff.added_folders.push(path.clone());
+            }
+        }
+        for path in videos.iter().flatten() {
+            if !old_video_paths.contains(path.as_str()) {
+                diff.added_videos.push(path.clone());
+            } else if path_exists(path) {
+                diff.refreshed_videos.push(path.clone());
+            }
+        }
+        if let Some((deleted_dirs, deleted_videos)) = deleted {
+            diff.removed_folders = deleted_dirs.iter().map(|dir| dir.path.clone()).collect();
+            diff.removed_videos = deleted_videos.iter().map(|vid| vid.path.clone()).collect();
+        }
+
+        for paths in [
+            &mut diff.added_folders,
+            &mut diff.added_videos,
+            &mut diff.removed_folders,
+            &mut diff.removed_videos,
+            &mut diff.refreshed_videos,
+        ] {
+            paths.sort();
+        }
+        diff
+    }
+
+    /// moves the removals into a diff of their own
+    pub fn take_removals(&mut self) -> Self {
+        Self {
+            dir: self.dir.clone(),
+            parent_path: self.parent_path.clone(),
+            added_folders: Vec::new(),
+            added_videos: Vec::new(),
+            removed_folders: std::mem::take(&mut self.removed_folders),
+            removed_videos: std::mem::take(&mut self.removed_videos),
+            refreshed_videos: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added_folders.is_empty()
+            && self.added_videos.is_empty()
+            && self.removed_folders.is_empty()
+            && self.removed_videos.is_empty()
+            && self.refreshed_videos.is_empty()
+    }
+}
+
+/// the changes `upsert_read_os_dir` would make to the folder, without making them,
+/// so deletions can be confirmed first, eg: when a drive is only half mounted.
+#[command]
+pub fn preview_library_diff(
+    handle: AppHandle,
+    dir: String,
+    parent_path: Option<String>,
+    user: User,
+) -> Result<LibraryDiff, MpvShelfError> {
+    let ignore = LibraryIgnore::new(&user.settings.ignore_patterns, Path::new(&dir));
+    let (mut old_dirs, mut old_videos) = get_os_folder_entries(&handle, &dir)?;
+    let stale_entries =
+        find_stale_entries(&dir, &ignore, Some(&mut old_dirs), Some(&mut old_videos))?;
+
+    Ok(LibraryDiff::new(
+        dir,
+        parent_path,
+        &stale_entries,
+        &old_dirs,
+        &old_videos,
+    ))
+}
+
+/// applies a diff from `preview_library_diff`.
+/// entries that came back since the preview aren't removed.
+/// returns a bool to indicate whether a refetch should be performed
+#[command]
+pub async fn apply_library_diff(
+    handle: AppHandle,
+    user: User,
+    diff: LibraryDiff,
+    scan_id: Option<String>,
+) -> Result<bool, MpvShelfError> {
+    if diff.is_empty() {
+        return Ok(false);
+    }
+
+    let ignore = LibraryIgnore::new(&user.settings.ignore_patterns, Path::new(&diff.dir));
+    let still_removed = |path: &String, is_dir: bool| {
+        !path_exists(path) || ignore.is_ignored(Path::new(path), is_dir)
+    };
+    let removed_folders: Vec<OsFolder> = diff
+        .removed_folders
+        .iter()
+        .filter(|path| still_removed(path, true))
+        .filter_map(|path| get_os_folder_by_path(handle.clone(), path.clone()).ok())
+        .collect();
+    let removed_video_paths: Vec<String> = diff
+        .removed_videos
+        .into_iter()
+        .filter(|path| still_removed(path, false))
+        .collect();
+    let removed_videos = get_os_videos_by_paths(&handle, &removed_video_paths)?;
+
+    let added_videos = diff.added_videos.into_iter().chain(diff.refreshed_videos);
+    // `Some` even when empty, so only the previewed entries are read
+    let stale_entries = StaleEntries::Found {
+        dirs: Some(diff.added_folders.into_iter().collect()),
+        videos: Some(added_videos.collect()),
+        deleted: Some((removed_folders, removed_videos)),
+    };
+    // defaults to the folder's path, so the watcher's scans can be cancelled too
+    let scan = Scan::start(&handle, scan_id.unwrap_or_else(|| diff.dir.clone()));
+    sync_stale_entries(
+        handle,
+        diff.dir,
+        diff.parent_path,
+        user,
+        stale_entries,
+        &ignore,
+        &scan,
+    )
+    .await?;
 
     Ok(true)
 }
@@ -336,10 +779,23 @@ pub fn read_os_folder_dir(
     update_datetime: Option<(String, String)>,
     parent_path: Option<String>,
     stale_entries: StaleEntries,
+    ignore: &LibraryIgnore,
+    scan: &Scan,
 ) -> Result<FolderGroup, ReadDirError> {
+    scan.check_cancelled()?;
     let mut childfolder_paths = Vec::new();
     let mut video_paths = Vec::new();
-    read_dir_helper(&path, &mut childfolder_paths, &mut video_paths)?;
+    let mut subtitle_paths = Vec::new();
+    read_dir_helper(
+        &path,
+        ignore,
+        &mut childfolder_paths,
+        &mut video_paths,
+        &mut subtitle_paths,
+    )?;
+
+    // decided before stale filtering, which drops the unchanged files
+    let audiobook = !video_paths.is_empty() && video_paths.iter().all(|vp| is_audio_file(vp));
 
     let parent_path = parent_path.is_some().then(|| {
         Path::new(&path)
@@ -367,6 +823,7 @@ pub fn read_os_folder_dir(
         }
         // If stale_entries is `None`, do nothing, no filtering occurs.
     }
+    scan.dir_visited(video_paths.len());
     let os_folder_path_clone = path.clone();
     let os_folder = Path::new(&os_folder_path_clone);
     let (update_date, update_time) = update_datetime.clone().unwrap_or_else(get_date_time);
@@ -387,6 +844,10 @@ pub fn read_os_folder_dir(
                 &app_data_dir,
             )
             .ok()
+            .map(|mut vid| {
+                vid.subtitles = match_subtitles(&vid.path, &subtitle_paths);
+                vid
+            })
         })
         .collect::<Vec<OsVideo>>();
     total_videos.extend(current_folders_videos);
@@ -400,6 +861,7 @@ pub fn read_os_folder_dir(
     let child_folders_group: Vec<FolderGroup> = childfolder_paths
         .into_par_iter()
         .filter_map(|folder_path| {
+            let folder_ignore = ignore.child(Path::new(&folder_path));
             match read_os_folder_dir(
                 handle,
                 folder_path,
@@ -407,6 +869,8 @@ pub fn read_os_folder_dir(
                 update_datetime.clone(),
                 Some(path.clone()),
                 StaleEntries::None,
+                &folder_ignore,
+                scan,
             ) {
                 Ok(f) => Some(f),
                 Err(e) => {
@@ -438,6 +902,10 @@ pub fn read_os_folder_dir(
         parent_path,
         last_watched_video: first_video,
         cover_img_path: cover_img,
+        credits_offset: None,
+        profile: None,
+        tracks: None,
+        audiobook,
         update_date,
         update_time,
     };
@@ -531,14 +999,68 @@ pub fn join_cover_img_path(
     Ok(cover_img_full_path)
 }
 
-/// returns the full duration of the given video
+/// what ffmpeg reported about the input while extracting the cover img
+pub struct FfmpegOutput {
+    /// eg: `00:23:45.00`
+    pub duration: String,
+    pub chapters: Vec<Chapter>,
+}
+
+/// parses the chapter list ffmpeg prints for the input, eg:
+/// ```text
+///   Chapters:
+///     Chapter #0:0: start 0.000000, end 1234.567000
+///       Metadata:
+///         title           : Chapter 1
+/// ```
+fn parse_ffmpeg_chapters(stderr: &str) -> Vec<Chapter> {
+    let mut chapters: Vec<Chapter> = Vec::new();
+    let mut in_chapter = false;
+
+    for line in stderr.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("Chapter #") {
+            // skip the `0:0: ` stream id
+            let times = rest.split_once(": ").map_or(rest, |(_, times)| times);
+            let seconds = |key: &str| {
+                times
+                    .split(',')
+                    .find_map(|part| part.trim().strip_prefix(key))
+                    .and_then(|secs| secs.trim().parse::<f64>().ok())
+                    .map(|secs| secs as u64)
+            };
+            let (Some(start), Some(end)) = (seconds("start"), seconds("end")) else {
+                continue;
+            };
+            chapters.push(Chapter {
+                title: None,
+                start,
+                end,
+            });
+            in_chapter = true;
+        } else if line.starts_with("Stream #") || line.starts_with("Output #") {
+            in_chapter = false;
+        } else if in_chapter {
+            if let Some((key, value)) = line.split_once(':') {
+                if key.trim() == "title" {
+                    if let Some(chapter) = chapters.last_mut() {
+                        chapter.title = Some(value.trim().to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    chapters
+}
+
+/// returns the full duration & chapters of the given video
 /// while also extracting the cover img frame
 pub async fn ffmpeg_extract_frame(
     handle: &AppHandle,
     index: Option<usize>,
     entry_path: impl AsRef<str>,
     cover_img_path: &Path,
-) -> Result<String, FfmpegError> {
+) -> Result<FfmpegOutput, FfmpegError> {
     let frame_index = index.unwrap_or(5).to_string();
     // Arguments for ffmpeg to extract a frame and to retrieve video duration
     let args = [
@@ -572,12 +1094,21 @@ pub async fn ffmpeg_extract_frame(
                     }
                 }
                 stderr.push_str(&line);
+                // keep the lines apart for `parse_ffmpeg_chapters`
+                if !line.ends_with('\n') {
+                    stderr.push('\n');
+                }
             }
             CommandEvent::Error(e) => {
                 stderr.push_str(&format!("\nexit error:\n{}", e));
             }
             CommandEvent::Terminated(_) => match duration {
-                Some(dur) => return Ok(dur.to_string()),
+                Some(dur) => {
+                    return Ok(FfmpegOutput {
+                        duration: dur.to_string(),
+                        chapters: parse_ffmpeg_chapters(&stderr),
+                    })
+                }
                 None => return Err(FfmpegError::StdErr(stderr)),
             },
             _ => return Err(FfmpegError::ProcessInterrupted),
@@ -593,58 +1124,6 @@ pub fn normalize_path(path: &str) -> PathBuf {
     Path::new(&normalized).to_path_buf()
 }
 
-pub fn find_video_index(parent_path: &Path, selected_video_path: String) -> Result<u32, MpvError> {
-    //println!("looking for selected video: {selected_video_path}");
-    let mut media_files: Vec<fs::DirEntry> = fs::read_dir(parent_path)?
-        .filter_map(|entry| entry.ok())
-        .collect();
-    media_files.retain(|entry| {
-        if let Ok(metadata) = entry.metadata() {
-            if metadata.is_file() {
-                if let Some(extension) = entry.path().extension() {
-                    if let Some(extension_str) = extension.to_str() {
-                        let extension_str = extension_str.to_lowercase();
-                        return SUPPORTED_VIDEO_FORMATS.get_key(&extension_str).is_some()
-                            || SUPPORTED_AUDIO_FORMATS.get_key(&extension_str).is_some();
-                    }
-                }
-            }
-        }
-        false
-    });
-
-    media_files.par_sort_by(|a, b| {
-        let num_a = EPISODE_TITLE_REGEX
-            .captures(a.file_name().to_str().unwrap())
-            .and_then(|caps| caps.get(caps.len() - 1))
-            .and_then(|m| m.as_str().parse::<u32>().ok())
-            .unwrap_or(0);
-        let num_b = EPISODE_TITLE_REGEX
-            .captures(b.file_name().to_str().unwrap())
-            .and_then(|caps| caps.get(caps.len() - 1))
-            .and_then(|m| m.as_str().parse::<u32>().ok())
-            .unwrap_or(0);
-        num_a.cmp(&num_b)
-    });
-
-    // debug for the media files
-    //dbg!(&media_files);
-
-    let selected_video_file_name = match Path::new(&selected_video_path).file_name() {
-        Some(file_name) => file_name,
-        None => return Err(MpvError::InvalidPathName(selected_video_path)),
-    };
-
-    let current_video_index = media_files
-        .iter()
-        .position(|entry| entry.file_name() == selected_video_file_name);
-    //dbg!(current_video_index);
-    match current_video_index {
-        Some(index) => Ok(index as u32),
-        None => Err(MpvError::OsVideoNotFound(selected_video_path)),
-    }
-}
-
 #[command]
 pub async fn download_mpv_binary(handle: AppHandle) -> Result<String, HttpClientError> {
     let platform = env::consts::OS;
@@ -742,3 +1221,505 @@ pub fn show_in_folder(path: String) {
             .unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use tauri::test::{mock_app, MockRuntime};
+    use tauri::App;
+
+    use super::*;
+    use crate::database::data::v2::Settings;
+    use crate::database::{
+        add_bookmark, get_bookmarks, get_queue, get_user_by_id, init_database, save_queue,
+        save_user, FileMetadata,
+    };
+
+    /// a throwaway database with the default user in it
+    struct TestLibrary {
+        app: App<MockRuntime>,
+        dir: PathBuf,
+        user: User,
+    }
+
+    impl TestLibrary {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("mpvshelf-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            let app = mock_app();
+            init_database(&dir, app.handle()).unwrap();
+
+            let user = User {
+                id: "1".into(),
+                username: "default".into(),
+                settings: Settings::default(),
+                last_watched_video: None,
+            };
+            save_user(app.handle(), user.clone()).unwrap();
+
+            Self { app, dir, user }
+        }
+
+        fn handle(&self) -> &AppHandle<MockRuntime> {
+            self.app.handle()
+        }
+
+        /// saves the folders & videos, as the last scan left them
+        fn scanned(&self, folders: Vec<OsFolder>, videos: Vec<OsVideo>) {
+            update_os_folders(self.handle().clone(), folders).unwrap();
+            update_os_videos(self.handle().clone(), videos).unwrap();
+        }
+
+        /// the stale entries of a scan are read from the database,
+        /// & only exactly what's stored can be removed
+        fn saved_folder(&self, path: &str) -> OsFolder {
+            get_os_folder_by_path(self.handle().clone(), path.into()).unwrap()
+        }
+
+        fn saved_video(&self, path: &str) -> OsVideo {
+            get_os_videos_by_paths(self.handle(), &[path.into()])
+                .unwrap()
+                .remove(0)
+        }
+    }
+
+    impl Drop for TestLibrary {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn folder(path: &str) -> OsFolder {
+        OsFolder {
+            user_id: "1".into(),
+            path: path.into(),
+            title: Path::new(path)
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().to_string()),
+            parent_path: Path::new(path)
+                .parent()
+                .map(|parent| parent.to_string_lossy().to_string()),
+            last_watched_video: None,
+            cover_img_path: None,
+            credits_offset: None,
+            profile: None,
+            tracks: None,
+            audiobook: false,
+            update_date: String::new(),
+            update_time: String::new(),
+        }
+    }
+
+    /// a 24 minute episode, as read from a file of `size` bytes
+    fn episode(main_folder_path: &str, path: &str, size: u64, fingerprint: &str) -> OsVideo {
+        OsVideo {
+            duration: 1440,
+            metadata: Some(FileMetadata {
+                created: None,
+                modified: None,
+                accessed: None,
+                size: Some(size),
+                fingerprint: Some(fingerprint.into()),
+            }),
+            ..OsVideo::stub(main_folder_path, path)
+        }
+    }
+
+    fn subtitle(path: &str, lang: Option<&str>) -> Subtitle {
+        Subtitle {
+            path: path.into(),
+            lang: lang.map(String::from),
+        }
+    }
+
+    #[test]
+    fn matches_subtitles_by_the_video_name() {
+        let video = "/shows/Show/Show - 01.mkv";
+        let subtitle_paths: Vec<String> = [
+            "/shows/Show/Show - 01.srt",
+            "/shows/Show/Show - 01.ja.ass",
+            "/shows/Show/Show - 01.en.forced.srt",
+            "/shows/Show/Subs/Show - 01/2_English.srt",
+            // the next episode & another release of the same one
+            "/shows/Show/Show - 010.srt",
+            "/shows/Show/Show - 02.srt",
+            "/shows/Show/[Group] Show - 01.srt",
+            "/shows/Show/Subs/Show - 02/2_English.srt",
+        ]
+        .map(String::from)
+        .to_vec();
+
+        assert_eq!(
+            match_subtitles(video, &subtitle_paths),
+            vec![
+                subtitle("/shows/Show/Show - 01.en.forced.srt", Some("en.forced")),
+                subtitle("/shows/Show/Show - 01.ja.ass", Some("ja")),
+                subtitle("/shows/Show/Show - 01.srt", None),
+                subtitle(
+                    "/shows/Show/Subs/Show - 01/2_English.srt",
+                    Some("2_English")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn loads_vobsubs_through_their_idx() {
+        let subtitle_paths: Vec<String> = ["/movies/Movie.idx", "/movies/Movie.sub"]
+            .map(String::from)
+            .to_vec();
+
+        assert_eq!(
+            match_subtitles("/movies/Movie.mkv", &subtitle_paths),
+            vec![subtitle("/movies/Movie.idx", None)]
+        );
+        // a `.sub` on its own is a MicroDVD subtitle
+        assert_eq!(
+            match_subtitles("/movies/Movie.mkv", &subtitle_paths[1..]),
+            vec![subtitle("/movies/Movie.sub", None)]
+        );
+    }
+
+    #[test]
+    fn a_new_subtitle_makes_its_video_stale() {
+        let dir = env::temp_dir().join(format!("mpvshelf-stale-subtitles-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let dir_path = dir.to_string_lossy().to_string();
+        let video_path = dir.join("01.mkv").to_string_lossy().to_string();
+        fs::write(&video_path, b"not really a video").unwrap();
+
+        let ignore = LibraryIgnore::new(&[], &dir);
+        let old_video = OsVideo {
+            metadata: FileMetadata::from_path(&video_path),
+            ..OsVideo::stub(&dir_path, &video_path)
+        };
+        let stale_entries = |old_video: &OsVideo| {
+            find_stale_entries(
+                &dir_path,
+                &ignore,
+                Some(&mut Vec::new()),
+                Some(&mut vec![old_video.clone()]),
+            )
+            .unwrap()
+        };
+        assert!(stale_entries(&old_video).is_none());
+
+        fs::write(
+            dir.join("01.en.srt"),
+            b"1\n00:00:01,000 --> 00:00:02,000\nhi\n",
+        )
+        .unwrap();
+        let found = stale_entries(&old_video);
+        let _ = fs::remove_dir_all(&dir);
+        let StaleEntries::Found { videos, .. } = found else {
+            panic!("the new subtitle wasn't noticed");
+        };
+        assert_eq!(videos, Some(HashSet::from([video_path])));
+    }
+
+    fn chapter(title: Option<&str>, start: u64, end: u64) -> Chapter {
+        Chapter {
+            title: title.map(String::from),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn parses_the_chapters_ffmpeg_prints() {
+        let stderr = "\
+Input #0, matroska,webm, from '/videos/Frieren S01/Frieren - 01.mkv':
+  Metadata:
+    title           : Frieren - 01
+    ENCODER         : Lavf60.16.100
+  Duration: 00:24:02.03, start: 0.000000, bitrate: 2911 kb/s
+  Chapters:
+    Chapter #0:0: start 0.000000, end 90.048000
+      Metadata:
+        title           : Opening
+    Chapter #0:1: start 90.048000, end 1350.016000
+      Metadata:
+        title           : Part A: The Journey's End
+    Chapter #0:2: start 1350.016000, end 1442.034000
+  Stream #0:0: Video: hevc (Main 10), yuv420p10le(tv), 1920x1080, SAR 1:1 DAR 16:9, 23.98 fps, 23.98 tbr, 1k tbn (default)
+      Metadata:
+        title           : Frieren
+        BPS             : 2500000
+  Stream #0:1(jpn): Audio: opus, 48000 Hz, stereo, fltp (default)
+      Metadata:
+        title           : Japanese
+Stream mapping:
+  Stream #0:0 -> #0:0 (hevc (native) -> png (native))
+Output #0, image2, to '/tmp/cover.png':
+  Metadata:
+    title           : Frieren - 01
+";
+        assert_eq!(
+            parse_ffmpeg_chapters(stderr),
+            vec![
+                chapter(Some("Opening"), 0, 90),
+                chapter(Some("Part A: The Journey's End"), 90, 1350),
+                // the stream titles below it aren't the chapter's
+                chapter(None, 1350, 1442),
+            ]
+        );
+    }
+
+    #[test]
+    fn videos_without_chapters_have_none() {
+        let stderr = "\
+Input #0, mov,mp4,m4a,3gp,3g2,mj2, from '/videos/clip.mp4':
+  Metadata:
+    major_brand     : isom
+    title           : Clip
+  Duration: 00:01:30.00, start: 0.000000, bitrate: 1200 kb/s
+  Stream #0:0[0x1](und): Video: h264 (High) (avc1 / 0x31637661), yuv420p(progressive), 1280x720, 1000 kb/s, 30 fps, 30 tbr, 15360 tbn (default)
+";
+        assert!(parse_ffmpeg_chapters(stderr).is_empty());
+        assert!(parse_ffmpeg_chapters("").is_empty());
+    }
+
+    #[test]
+    fn pairs_renamed_videos_by_fingerprint() {
+        let library = TestLibrary::new("pair-renamed");
+        let show = folder("/shows/Show");
+        let old = OsVideo {
+            position: 600,
+            in_progress: true,
+            ..episode(&show.path, "/shows/Show/01.mkv", 1024, "1024-aaaa")
+        };
+        library.scanned(vec![show.clone()], vec![old.clone()]);
+
+        let mut videos = vec![
+            episode(&show.path, "/shows/Show/Show - 01.mkv", 1024, "1024-aaaa"),
+            episode(&show.path, "/shows/Show/Show - 02.mkv", 1024, "1024-cccc"),
+        ];
+        let moves =
+            pair_moved_videos(library.handle(), "1", vec![old.clone()], &mut videos).unwrap();
+
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].0, old.path);
+        assert_eq!(moves[0].1.path, "/shows/Show/Show - 01.mkv");
+        assert_eq!((videos[0].position, videos[0].in_progress), (600, true));
+        // a new episode, not a moved one
+        assert_eq!((videos[1].position, videos[1].in_progress), (0, false));
+    }
+
+    #[test]
+    fn pairs_videos_moved_in_from_other_folders() {
+        let library = TestLibrary::new("pair-moved");
+        let specials = folder("/shows/Specials");
+        let show = folder("/shows/Show");
+        // the specials folder wasn't rescanned, so its video is still saved
+        let old = OsVideo {
+            watched: true,
+            ..episode(&specials.path, "/shows/Specials/OVA.mkv", 2048, "2048-aaaa")
+        };
+        let other_user = OsVideo {
+            user_id: "2".into(),
+            position: 300,
+            ..episode(
+                &specials.path,
+                "/shows/Specials/OVA 2.mkv",
+                2048,
+                "2048-bbbb",
+            )
+        };
+        library.scanned(
+            vec![specials.clone(), show.clone()],
+            vec![old.clone(), other_user],
+        );
+
+        let mut videos = vec![
+            episode(&show.path, "/shows/Show/OVA.mkv", 2048, "2048-aaaa"),
+            episode(&show.path, "/shows/Show/OVA 2.mkv", 2048, "2048-bbbb"),
+        ];
+        let moves = pair_moved_videos(library.handle(), "1", Vec::new(), &mut videos).unwrap();
+
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].0, old.path);
+        assert!(videos[0].watched);
+        // only the user's own videos are paired
+        assert_eq!(videos[1].position, 0);
+    }
+
+    #[test]
+    fn pairs_lookalike_videos_by_their_own_fingerprint() {
+        let library = TestLibrary::new("pair-ambiguous");
+        let show = folder("/shows/Show");
+        // same size & duration, only the fingerprint tells them apart
+        let first = OsVideo {
+            position: 100,
+            ..episode(&show.path, "/shows/Show/01.mkv", 1024, "1024-aaaa")
+        };
+        let second = OsVideo {
+            position: 200,
+            ..episode(&show.path, "/shows/Show/02.mkv", 1024, "1024-bbbb")
+        };
+        library.scanned(vec![show.clone()], vec![first.clone(), second.clone()]);
+
+        // renamed in the opposite order
+        let mut videos = vec![
+            episode(&show.path, "/shows/Show/Show - 02.mkv", 1024, "1024-bbbb"),
+            episode(&show.path, "/shows/Show/Show - 01.mkv", 1024, "1024-aaaa"),
+        ];
+        let moves = pair_moved_videos(
+            library.handle(),
+            "1",
+            vec![first.clone(), second.clone()],
+            &mut videos,
+        )
+        .unwrap();
+
+        let moves: Vec<(&str, &str)> = moves
+            .iter()
+            .map(|(old_path, vid)| (old_path.as_str(), vid.path.as_str()))
+            .collect();
+        assert_eq!(
+            moves,
+            vec![
+                (second.path.as_str(), "/shows/Show/Show - 02.mkv"),
+                (first.path.as_str(), "/shows/Show/Show - 01.mkv"),
+            ]
+        );
+        assert_eq!((videos[0].position, videos[1].position), (200, 100));
+    }
+
+    #[test]
+    fn migrates_everything_that_referenced_moved_videos() {
+        let library = TestLibrary::new("migrate-moved");
+        let handle = library.handle();
+        let show = folder("/shows/Show");
+        let old = episode(&show.path, "/shows/Show/01.mkv", 1024, "1024-aaaa");
+        let next = episode(&show.path, "/shows/Show/02.mkv", 1024, "1024-bbbb");
+        library.scanned(
+            vec![OsFolder {
+                last_watched_video: Some(old.clone()),
+                ..show.clone()
+            }],
+            vec![old.clone(), next.clone()],
+        );
+        let mut queue = get_queue(handle, "1").unwrap();
+        queue.video_paths = vec![old.path.clone(), next.path.clone()];
+        save_queue(handle, queue).unwrap();
+        let user = User {
+            last_watched_video: Some(old.clone()),
+            ..library.user.clone()
+        };
+        save_user(handle, user.clone()).unwrap();
+        add_bookmark(handle.clone(), old.path.clone(), 300, None, None).unwrap();
+
+        let mut renamed = vec![episode(
+            &show.path,
+            "/shows/Show/Show - 01.mkv",
+            1024,
+            "1024-aaaa",
+        )];
+        let moves = replace_stale_entries(
+            handle,
+            &user,
+            Some((Vec::new(), vec![library.saved_video(&old.path)])),
+            &mut renamed,
+        )
+        .unwrap();
+        update_os_videos(handle.clone(), renamed.clone()).unwrap();
+        migrate_moved_videos(handle, &moves, user).unwrap();
+
+        let new_path = renamed[0].path.clone();
+        assert!(
+            get_os_videos_by_paths(handle, std::slice::from_ref(&old.path))
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            get_queue(handle, "1").unwrap().video_paths,
+            vec![new_path.clone(), next.path.clone()]
+        );
+        let lwv_path = |lwv: Option<OsVideo>| lwv.map(|vid| vid.path);
+        assert_eq!(
+            lwv_path(library.saved_folder(&show.path).last_watched_video),
+            Some(new_path.clone())
+        );
+        let saved_user = get_user_by_id(handle.clone(), "1".into()).unwrap();
+        assert_eq!(
+            lwv_path(saved_user.last_watched_video),
+            Some(new_path.clone())
+        );
+        assert_eq!(get_bookmarks(handle.clone(), new_path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn moved_videos_keep_their_bookmarks() {
+        let library = TestLibrary::new("moved-bookmarks");
+        let handle = library.handle();
+        let show = folder("/shows/Show");
+        library.scanned(
+            vec![show.clone()],
+            vec![episode(&show.path, "/shows/Show/01.mkv", 1024, "1024-aaaa")],
+        );
+        let old = library.saved_video("/shows/Show/01.mkv");
+        add_bookmark(
+            handle.clone(),
+            old.path.clone(),
+            300,
+            Some("OP".into()),
+            None,
+        )
+        .unwrap();
+
+        // renamed in the same folder
+        let mut renamed = vec![episode(
+            &show.path,
+            "/shows/Show/Show - 01.mkv",
+            1024,
+            "1024-aaaa",
+        )];
+        let moves = replace_stale_entries(
+            handle,
+            &library.user,
+            Some((Vec::new(), vec![old.clone()])),
+            &mut renamed,
+        )
+        .unwrap();
+        assert_eq!(moves.len(), 1);
+        update_os_videos(handle.clone(), renamed.clone()).unwrap();
+        assert!(get_bookmarks(handle.clone(), old.path.clone())
+            .unwrap()
+            .is_empty());
+        let bookmarks = get_bookmarks(handle.clone(), renamed[0].path.clone()).unwrap();
+        assert_eq!(
+            bookmarks
+                .iter()
+                .map(|bookmark| (bookmark.timestamp, bookmark.name.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(300, "OP")]
+        );
+
+        // & again along with its folder, which deletes the old folder's videos
+        let renamed_show = folder("/shows/Show (2024)");
+        let mut moved = vec![episode(
+            &renamed_show.path,
+            "/shows/Show (2024)/Show - 01.mkv",
+            1024,
+            "1024-aaaa",
+        )];
+        replace_stale_entries(
+            handle,
+            &library.user,
+            Some((vec![library.saved_folder(&show.path)], Vec::new())),
+            &mut moved,
+        )
+        .unwrap();
+        assert!(get_bookmarks(handle.clone(), renamed[0].path.clone())
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            get_bookmarks(handle.clone(), moved[0].path.clone())
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::database::data::v2::Track;
+use crate::error::MpvIpcError;
+
+///! Client for mpv's JSON IPC protocol.
+///! https://mpv.io/manual/master/#json-ipc
+
+#[cfg(not(windows))]
+type IpcStream = std::os::unix::net::UnixStream;
+#[cfg(windows)]
+type IpcStream = std::fs::File;
+
+/// Every property mpvshelf observes on a spawned mpv instance;
+/// the index of each property is used as its observer id.
+pub const OBSERVED_PROPERTIES: [&str; 9] = [
+    "path",
+    "time-pos",
+    "duration",
+    "playlist-pos",
+    "aid",
+    "sid",
+    "current-tracks/audio/lang",
+    "current-tracks/sub/lang",
+    "speed",
+];
+
+/// Structured events read from mpv's ipc socket.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MpvEvent {
+    /// mpv started playing a new file from the playlist.
+    Path(String),
+    /// * in seconds.
+    TimePos(f64),
+    /// * in seconds.
+    Duration(f64),
+    /// mpv moved to another playlist entry, `-1` once there's none left
+    PlaylistPos(i64),
+    Aid(Track),
+    Sid(Track),
+    /// `None` when the selected track has no language
+    AudioLang(Option<String>),
+    SubLang(Option<String>),
+    Speed(f64),
+    /// a `script-message` from a script, eg: `mpvshelf.lua`
+    ClientMessage(Vec<String>),
+    /// the current file is ready for tracks to be added
+    FileLoaded,
+    EndFile,
+    Shutdown,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMessage {
+    event: Option<String>,
+    name: Option<String>,
+    data: Option<Value>,
+    args: Option<Vec<String>>,
+}
+
+impl MpvEvent {
+    /// returns `None` for command replies & events mpvshelf doesn't care about
+    pub fn from_line(line: &str) -> Result<Option<Self>, MpvIpcError> {
+        let msg: RawMessage = serde_json::from_str(line)?;
+        if msg.event.as_deref() == Some("client-message") {
+            return Ok(Some(MpvEvent::ClientMessage(msg.args.unwrap_or_default())));
+        }
+
+        let event = match (msg.event.as_deref(), msg.name.as_deref(), msg.data) {
+            (Some("property-change"), Some("path"), Some(Value::String(path))) => {
+                Some(MpvEvent::Path(path))
+            }
+            (Some("property-change"), Some("time-pos"), Some(data)) => {
+                data.as_f64().map(MpvEvent::TimePos)
+            }
+            (Some("property-change"), Some("duration"), Some(data)) => {
+                data.as_f64().map(MpvEvent::Duration)
+            }
+            (Some("property-change"), Some("playlist-pos"), Some(data)) => {
+                data.as_i64().map(MpvEvent::PlaylistPos)
+            }
+            (Some("property-change"), Some("aid"), Some(data)) => {
+                track_from_value(&data).map(MpvEvent::Aid)
+            }
+            (Some("property-change"), Some("sid"), Some(data)) => {
+                track_from_value(&data).map(MpvEvent::Sid)
+            }
+            (Some("property-change"), Some("current-tracks/audio/lang"), data) => Some(
+                MpvEvent::AudioLang(data.and_then(|d| d.as_str().map(String::from))),
+            ),
+            (Some("property-change"), Some("current-tracks/sub/lang"), data) => Some(
+                MpvEvent::SubLang(data.and_then(|d| d.as_str().map(String::from))),
+            ),
+            (Some("property-change"), Some("speed"), Some(data)) => {
+                data.as_f64().map(MpvEvent::Speed)
+            }
+            (Some("file-loaded"), _, _) => Some(MpvEvent::FileLoaded),
+            (Some("end-file"), _, _) => Some(MpvEvent::EndFile),
+            (Some("shutdown"), _, _) => Some(MpvEvent::Shutdown),
+            _ => None,
+        };
+
+        Ok(event)
+    }
+}
+
+/// mpv reports a deselected track as `false`
+fn track_from_value(data: &Value) -> Option<Track> {
+    match data {
+        Value::Bool(false) => Some(Track::Off),
+        Value::String(s) if s == "no" => Some(Track::Off),
+        _ => data.as_i64().map(Track::Id),
+    }
+}
+
+/// Returns the ipc server path for a player session.
+/// A Unix socket in the temp dir, or a named pipe on windows.
+pub fn ipc_socket_path(session_id: &str) -> String {
+    #[cfg(windows)]
+    {
+        format!(r"\\.\pipe\mpvshelf-{session_id}")
+    }
+    #[cfg(not(windows))]
+    {
+        std::env::temp_dir()
+            .join(format!("mpvshelf-{session_id}.sock"))
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+fn open_stream(socket_path: &str) -> io::Result<IpcStream> {
+    #[cfg(windows)]
+    {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(socket_path)
+    }
+    #[cfg(not(windows))]
+    {
+        IpcStream::connect(socket_path)
+    }
+}
+
+pub struct MpvIpcClient {
+    reader: BufReader<IpcStream>,
+    writer: IpcStream,
+}
+
+impl MpvIpcClient {
+    /// mpv creates the socket shortly after it starts,
+    /// so keep retrying until the `timeout` runs out.
+    pub fn connect(socket_path: &str, timeout: Duration) -> Result<Self, MpvIpcError> {
+        let instant = Instant::now();
+        loop {
+            match open_stream(socket_path) {
+                Ok(stream) => {
+                    let writer = stream.try_clone()?;
+                    return Ok(Self {
+                        reader: BufReader::new(stream),
+                        writer,
+                    });
+                }
+                Err(_) if instant.elapsed() < timeout => {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(_) => return Err(MpvIpcError::ConnectTimeout(socket_path.to_string())),
+            }
+        }
+    }
+
+    /// sends a raw command, eg: `json!(["set_property", "pause", true])`
+    pub fn command(&mut self, args: Value) -> Result<(), MpvIpcError> {
+        let mut msg = json!({ "command": args }).to_string();
+        msg.push('\n');
+        self.writer.write_all(msg.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn observe_properties(&mut self) -> Result<(), MpvIpcError> {
+        for (id, name) in OBSERVED_PROPERTIES.iter().enumerate() {
+            self.command(json!(["observe_property", id + 1, name]))?;
+        }
+        Ok(())
+    }
+
+    /// Blocks until the next relevant event.
+    /// Returns `None` once mpv has shut down & closed the socket.
+    pub fn next_event(&mut self) -> Result<Option<MpvEvent>, MpvIpcError> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::ConnectionReset | io::ErrorKind::BrokenPipe
+                    ) =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(MpvIpcError::Io(e)),
+            }
+
+            if let Some(event) = MpvEvent::from_line(line.trim())? {
+                return Ok(Some(event));
+            }
+        }
+    }
+}
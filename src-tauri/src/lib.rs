@@ -8,19 +8,43 @@ use tray::kill_dup_process;
 mod database;
 mod error;
 mod fs;
+mod ipc;
+mod library_ignore;
 mod misc;
 mod mpv;
+mod playback;
+mod player;
+mod plugins;
+mod queue;
+mod scan;
 mod tray;
+mod watch_later;
+mod watcher;
 mod data;
 
 use crate::database::{
-    delete_os_folders, get_default_user, get_os_folder_by_path, get_os_folders,
-    get_os_folders_by_path, get_os_videos, get_user_by_id, update_os_folders, update_os_videos,
+    add_bookmark, delete_bookmark, delete_os_folders, get_bookmarks, get_default_user,
+    get_mpv_plugins, get_os_folder_by_path, get_os_folders, get_os_folders_by_path, get_os_videos,
+    get_playback_profile, get_user_by_id, update_bookmark, update_os_folders, update_os_videos,
     update_user,
 };
-use crate::fs::{check_cover_img_exists, download_mpv_binary, show_in_folder, upsert_read_os_dir};
+use crate::fs::{
+    apply_library_diff, check_cover_img_exists, download_mpv_binary, preview_library_diff,
+    show_in_folder, upsert_read_os_dir,
+};
 use crate::mpv::{mpv_system_check, play_video};
+use crate::playback::{
+    cancel_sleep_timer, get_playback_session, set_sleep_timer, stop_playback, PlaybackState,
+};
+use crate::plugins::{disable_mpv_plugin, enable_mpv_plugin, install_mpv_plugin, remove_mpv_plugin};
+use crate::queue::{
+    clear_queue, enqueue_videos, get_queued_videos, move_queued_video, play_queue,
+    remove_queued_video,
+};
+use crate::scan::{cancel_scan, ScanState};
 use crate::tray::init_tray;
+use crate::watch_later::import_watch_later;
+use crate::watcher::{watch_library, LibraryWatcher};
 use crate::data::export_portable_config;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -35,6 +59,12 @@ pub fn run() {
             init_database(&app_data_dir, handle).unwrap();
             kill_dup_process();
             init_tray(app).unwrap();
+            app.manage(PlaybackState::default());
+            app.manage(ScanState::default());
+            app.manage(LibraryWatcher::default());
+            if let Err(e) = watch_library(handle) {
+                eprintln!("failed to watch the library: {e}");
+            }
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -53,10 +83,34 @@ pub fn run() {
             show_in_folder,
             mpv_system_check,
             play_video,
+            get_playback_session,
+            stop_playback,
             download_mpv_binary,
             upsert_read_os_dir,
             create_default_user,
             export_portable_config,
+            get_mpv_plugins,
+            install_mpv_plugin,
+            enable_mpv_plugin,
+            disable_mpv_plugin,
+            remove_mpv_plugin,
+            import_watch_later,
+            get_playback_profile,
+            get_bookmarks,
+            add_bookmark,
+            update_bookmark,
+            delete_bookmark,
+            set_sleep_timer,
+            cancel_sleep_timer,
+            get_queued_videos,
+            enqueue_videos,
+            move_queued_video,
+            remove_queued_video,
+            clear_queue,
+            play_queue,
+            cancel_scan,
+            preview_library_diff,
+            apply_library_diff,
         ])
         .build(tauri::generate_context!())
         .expect("error while running tauri application")
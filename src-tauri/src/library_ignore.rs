@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+///! `.mpvshelfignore` files & the global ignore patterns in `Settings`,
+///! both in gitignore syntax, eg:
+///! ```text
+///! Extras/
+///! NC*
+///! !NCOP 2.mkv
+///! ```
+///! An ignore file applies to its own folder & every folder below it.
+///! The global patterns are matched against file & folder names only.
+
+pub const IGNORE_FILE_NAME: &str = ".mpvshelfignore";
+
+#[derive(Clone)]
+pub struct LibraryIgnore {
+    global: Gitignore,
+    /// from the outermost folder in
+    folders: Vec<Gitignore>,
+}
+
+impl LibraryIgnore {
+    /// loads the ignore files of `dir` & all of its parent folders
+    pub fn new(global_patterns: &[String], dir: &Path) -> Self {
+        let mut builder = GitignoreBuilder::new("");
+        for pattern in global_patterns {
+            if let Err(e) = builder.add_line(None, pattern) {
+                eprintln!("invalid ignore pattern {pattern}: {e}");
+            }
+        }
+        let global = builder.build().unwrap_or_else(|e| {
+            eprintln!("failed to build the global ignore patterns: {e}");
+            Gitignore::empty()
+        });
+
+        let mut ancestors: Vec<&Path> = dir.ancestors().collect();
+        ancestors.reverse();
+        ancestors.into_iter().fold(
+            Self {
+                global,
+                folders: Vec::new(),
+            },
+            |ignore, ancestor| ignore.child(ancestor),
+        )
+    }
+
+    /// adds the ignore file of a folder below the last one, if it has one
+    pub fn child(&self, dir: &Path) -> Self {
+        let mut ignore = self.clone();
+        let ignore_file = dir.join(IGNORE_FILE_NAME);
+        if ignore_file.is_file() {
+            let (folder, error) = Gitignore::new(&ignore_file);
+            if let Some(e) = error {
+                eprintln!("failed to read {}: {e}", ignore_file.display());
+            }
+            ignore.folders.push(folder);
+        }
+        ignore
+    }
+
+    /// the closest ignore file decides, so a folder can un-ignore
+    /// what a parent folder or the global patterns ignore.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for folder in self.folders.iter().rev() {
+            if !path.starts_with(folder.path()) {
+                continue;
+            }
+            match folder.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+
+        path.file_name()
+            .is_some_and(|name| self.global.matched(Path::new(name), is_dir).is_ignore())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    }
+
+    #[test]
+    fn global_patterns_match_names_anywhere() {
+        let ignore = LibraryIgnore::new(
+            // an invalid pattern doesn't take the valid ones with it
+            &patterns(&["Extras/", "NC*", "*.nfo", "[oops"]),
+            Path::new("/library-ignore-global/Show"),
+        );
+        let show = Path::new("/library-ignore-global/Show");
+
+        assert!(ignore.is_ignored(&show.join("Extras"), true));
+        assert!(ignore.is_ignored(&show.join("Season 1/Extras"), true));
+        // `Extras/` only matches folders
+        assert!(!ignore.is_ignored(&show.join("Extras"), false));
+        assert!(ignore.is_ignored(&show.join("Season 1/NCOP 1.mkv"), false));
+        assert!(ignore.is_ignored(&show.join("tvshow.nfo"), false));
+        assert!(!ignore.is_ignored(&show.join("Season 1/01.mkv"), false));
+    }
+
+    #[test]
+    fn the_closest_ignore_file_decides() {
+        let root = env::temp_dir().join(format!("mpvshelf-library-ignore-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let season_1 = root.join("Season 1");
+        let season_2 = root.join("Season 2");
+        fs::create_dir_all(&season_1).unwrap();
+        fs::create_dir_all(&season_2).unwrap();
+        fs::write(root.join(IGNORE_FILE_NAME), "Extras/\nNC*\n").unwrap();
+        fs::write(season_1.join(IGNORE_FILE_NAME), "!NCOP 2.mkv\n").unwrap();
+
+        let ignore = LibraryIgnore::new(&patterns(&["*.txt"]), &root).child(&season_1);
+        assert!(ignore.is_ignored(&root.join("Extras"), true));
+        assert!(ignore.is_ignored(&season_1.join("NCOP 1.mkv"), false));
+        assert!(!ignore.is_ignored(&season_1.join("NCOP 2.mkv"), false));
+        // a folder's ignore file doesn't apply to its siblings
+        assert!(ignore.is_ignored(&season_2.join("NCOP 2.mkv"), false));
+        assert!(!ignore.is_ignored(&season_1.join("01.mkv"), false));
+        assert!(ignore.is_ignored(&season_1.join("notes.txt"), false));
+
+        // starting from a nested folder still loads its parents' ignore files
+        let nested = LibraryIgnore::new(&[], &season_1);
+        assert!(nested.is_ignored(&season_1.join("NCED 1.mkv"), false));
+        assert!(!nested.is_ignored(&season_1.join("NCOP 2.mkv"), false));
+
+        // & an ignore file can un-ignore what the global patterns ignore
+        fs::write(season_2.join(IGNORE_FILE_NAME), "!notes.txt\n").unwrap();
+        let ignore = ignore.child(&season_2);
+        assert!(!ignore.is_ignored(&season_2.join("notes.txt"), false));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
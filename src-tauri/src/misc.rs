@@ -29,4 +29,49 @@ pub fn get_date_time() -> (String, String) {
     (String::new(), String::new())
 }
 
+/// formats seconds as `hh:mm:ss`
+pub fn format_timestamp(secs: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+/// SplitMix64, small & stable across platforms,
+/// so a seed always gives the same order.
+pub struct SeededRng(u64);
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// seeds from the current time, kept within 53 bits
+    /// so the seed survives a round trip through a js number
+    pub fn random_seed() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |dur| dur.as_nanos() as u64)
+            & ((1 << 53) - 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
 
+    /// a random sort key that only depends on the seed & `item`,
+    /// so items can be added or removed without reordering the rest
+    pub fn key(seed: u64, item: &str) -> u64 {
+        // FNV-1a, std's hashers aren't stable across releases
+        let hash = item.bytes().fold(0xCBF2_9CE4_8422_2325, |hash: u64, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01B3)
+        });
+        Self::new(seed ^ hash).next_u64()
+    }
+}
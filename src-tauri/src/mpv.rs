@@ -1,18 +1,27 @@
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use regex::Regex;
 use tauri::path::BaseDirectory;
 use tauri::{command, AppHandle, Manager};
-
-use crate::database::data::v1::{OsFolder, OsVideo, User};
-use crate::database::{update_os_folders, update_os_videos, update_user};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+
+use crate::database::data::v2::{
+    Bookmark, MpvSettings, OsFolder, OsVideo, ResumeMode, TrackSelection, User,
+};
+use crate::database::{
+    get_os_folder_by_path, get_os_videos_in_tree, resolve_playback_profile, SortType,
+};
 use crate::error::{MpvError, MpvStdoutError};
-use crate::fs::{find_video_index, normalize_path};
-use crate::tray::build_window;
+use crate::misc::{format_timestamp, SeededRng};
+use crate::playback::{start_session, PlaybackSessionInfo, PlaybackState, PlaybackTracker};
+use crate::player::{player_backend, PlayRequest, PlayerSession};
+use crate::plugins::plugin_args;
+use rayon::slice::ParallelSliceMut;
+use serde::{Deserialize, Serialize};
 use std::num::ParseIntError;
-use std::path::Path;
-use std::process::{Child, Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::str::FromStr;
 use std::sync::LazyLock;
-use std::{io, time};
+use std::{env, fs, io, time};
 
 pub static EPISODE_TITLE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
     regex::Regex::new(
@@ -21,28 +30,28 @@ pub static EPISODE_TITLE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
     .unwrap()
 });
 
-#[derive(Debug, Clone)]
-pub enum TimestampType {
-    Duration,
-    Position,
-}
-
 #[derive(Debug, Clone)]
 pub struct MpvPlaybackData {
     /// Title of the last video played
-    last_video_path: String,
+    pub last_video_path: String,
     /// Timestamp of the last position watched
-    last_video_position: u64,
+    pub last_video_position: u64,
     /// Total duration of the last video
-    last_video_duration: u64,
+    pub last_video_duration: u64,
+    /// Index of the last video in mpv's playlist
+    pub playlist_pos: Option<i64>,
+    /// Tracks picked while the last video was loaded
+    pub tracks: TrackSelection,
 }
 
 impl MpvPlaybackData {
-    fn new() -> Self {
+    pub fn new() -> Self {
         MpvPlaybackData {
             last_video_path: String::from(""),
             last_video_position: 0,
             last_video_duration: 0,
+            playlist_pos: None,
+            tracks: TrackSelection::default(),
         }
     }
 
@@ -72,228 +81,705 @@ impl MpvPlaybackData {
         let total_seconds = hours * 3600 + minutes * 60 + seconds;
         Ok(total_seconds)
     }
+}
 
-    pub fn update_timestamp(
-        &mut self,
-        ts_type: TimestampType,
-        ts: String,
-    ) -> Result<u64, MpvStdoutError> {
-        // First handle the potential decimal portion from FFmpeg
-        let base_timestamp = ts
-            .split('.')
-            .next()
-            .ok_or_else(|| MpvStdoutError::InvalidTimestamp(ts.clone()))?;
+/// `current-tracks/*` & `--script-opts-append` were added in mpv 0.33
+pub const MIN_MPV_VERSION: MpvVersion = MpvVersion {
+    major: 0,
+    minor: 33,
+    patch: 0,
+};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct MpvVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
 
-        let parts: Vec<&str> = base_timestamp.split(':').collect();
-        if parts.len() != 3 {
-            return Err(MpvStdoutError::InvalidTimestamp(ts));
-        }
+impl MpvVersion {
+    /// parses `0.38.0` out of eg: `v0.38.0-451-g1a2b3c4` or `0.34.1+git`
+    fn parse(version: &str) -> Option<Self> {
+        let version = version.trim_start_matches('v');
+        let mut parts = version
+            .split(|c: char| !c.is_ascii_digit())
+            .take(3)
+            .map(|part| part.parse::<u32>().ok());
+
+        Some(MpvVersion {
+            major: parts.next()??,
+            minor: parts.next()??,
+            patch: parts.next().flatten().unwrap_or(0),
+        })
+    }
+}
 
-        let hours: u64 = parts[0].parse().map_err(|e: ParseIntError| {
-            MpvStdoutError::ParseInt(parts[0].to_string(), e.to_string())
-        })?;
-        let minutes: u64 = parts[1].parse().map_err(|e: ParseIntError| {
-            MpvStdoutError::ParseInt(parts[1].to_string(), e.to_string())
-        })?;
-        let seconds: u64 = parts[2].parse().map_err(|e: ParseIntError| {
-            MpvStdoutError::ParseInt(parts[2].to_string(), e.to_string())
-        })?;
+impl std::fmt::Display for MpvVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
 
-        let total_seconds = hours * 3600 + minutes * 60 + seconds;
-        match ts_type {
-            TimestampType::Position => self.last_video_position = total_seconds,
-            TimestampType::Duration => self.last_video_duration = total_seconds,
-        }
-        Ok(total_seconds)
+/// What the installed mpv build supports, as reported by `mpv --version` & `mpv --list-options`.
+#[derive(Serialize, Debug, Clone)]
+pub struct MpvCapabilities {
+    /// absolute path of the mpv binary
+    pub path: String,
+    /// `None` when the version string couldn't be parsed, eg: custom builds
+    pub version: Option<MpvVersion>,
+    pub libplacebo_version: Option<String>,
+    pub ffmpeg_version: Option<String>,
+    /// `--input-ipc-server`, needed to track playback
+    pub ipc: bool,
+    /// needed for `mpvshelf.lua` & lua plugins
+    pub lua: bool,
+}
+
+/// finds `mpv_exe` in `PATH` unless it's already a path
+fn resolve_mpv_path(mpv_exe: &str) -> Option<PathBuf> {
+    let exe = Path::new(mpv_exe);
+    if exe.components().count() > 1 {
+        return exe.canonicalize().ok();
     }
+
+    let exe_name = if cfg!(windows) && exe.extension().is_none() {
+        format!("{mpv_exe}.exe")
+    } else {
+        mpv_exe.to_string()
+    };
+    env::var_os("PATH")
+        .iter()
+        .flat_map(env::split_paths)
+        .map(|dir| dir.join(&exe_name))
+        .find(|path| path.is_file())
+        .and_then(|path| path.canonicalize().ok())
+}
+
+/// returns the value after `prefix` on the first line starting with it
+fn version_line(stdout: &str, prefix: &str) -> Option<String> {
+    stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix(prefix))
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(String::from)
 }
 
 #[command]
-pub fn mpv_system_check(mpv_path: Option<String>) -> Result<(), MpvError> {
+pub fn mpv_system_check(mpv_path: Option<String>) -> Result<MpvCapabilities, MpvError> {
     let mpv_exe = mpv_path.as_deref().unwrap_or("mpv").to_string();
+    let path = resolve_mpv_path(&mpv_exe)
+        .ok_or_else(|| MpvError::AbsolutePathNotFound(mpv_exe.clone()))?;
 
-    let output = Command::new(&mpv_exe).arg("--version").output();
-    match output {
-        Ok(output) => {
-            if output.status.success() {
-                Ok(())
-            } else {
-                Err(MpvError::AbsolutePathNotFound(mpv_exe))
-            }
-        }
+    let output = match Command::new(&path).arg("--version").output() {
+        Ok(output) if output.status.success() => output,
+        Ok(_) => return Err(MpvError::AbsolutePathNotFound(mpv_exe)),
         Err(e) if e.kind() == io::ErrorKind::NotFound => {
-            Err(MpvError::AbsolutePathNotFound(mpv_exe))
+            return Err(MpvError::AbsolutePathNotFound(mpv_exe))
+        }
+        Err(e) => return Err(MpvError::IoError(e)),
+    };
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    // eg: `mpv v0.38.0 Copyright © 2000-2024 mpv/MPlayer/mplayer2 projects`
+    let version_str = version_line(&stdout, "mpv ");
+    let version = version_str.as_deref().and_then(MpvVersion::parse);
+    if let Some(version) = version {
+        if version < MIN_MPV_VERSION {
+            return Err(MpvError::VersionTooOld(
+                version.to_string(),
+                MIN_MPV_VERSION.to_string(),
+            ));
+        }
+    }
+
+    // options only exist when mpv was built with the feature behind them
+    let options = Command::new(&path).arg("--list-options").output()?;
+    let options = String::from_utf8_lossy(&options.stdout);
+    let has_option = |name: &str| {
+        options
+            .lines()
+            .any(|line| line.split_whitespace().next() == Some(name))
+    };
+
+    Ok(MpvCapabilities {
+        path: path.to_string_lossy().to_string(),
+        version,
+        libplacebo_version: version_line(&stdout, "libplacebo version:"),
+        ffmpeg_version: version_line(&stdout, "FFmpeg version:"),
+        ipc: has_option("--input-ipc-server"),
+        lua: has_option("--osc"),
+    })
+}
+
+/// which videos to shuffle
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShuffleMode {
+    All,
+    Unwatched,
+    /// every video in the folder & its child folders
+    FolderTree,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Shuffle {
+    pub mode: ShuffleMode,
+    /// the same seed always gives the same order,
+    /// `None` starts a new shuffle with a random seed
+    pub seed: Option<u64>,
+}
+
+impl Shuffle {
+    /// shuffles `os_videos` in place & returns the seed it used.
+    /// a video's place only depends on the seed & its path,
+    /// so watching, adding or removing videos keeps the order of the rest.
+    fn apply(&mut self, os_videos: &mut Vec<OsVideo>) -> u64 {
+        let seed = *self.seed.get_or_insert_with(SeededRng::random_seed);
+        // the order has to be the same no matter how the frontend sorted it
+        os_videos.sort_by_cached_key(|vid| (SeededRng::key(seed, &vid.path), vid.path.clone()));
+
+        if self.mode == ShuffleMode::Unwatched {
+            os_videos.retain(|vid| !vid.watched);
         }
-        Err(e) => Err(MpvError::IoError(e)),
+        seed
     }
 }
 
+#[derive(Default)]
+pub struct LaunchOptions {
+    /// * in seconds.
+    pub start: Option<u64>,
+    /// overrides the folder's profile & the user's settings
+    pub autoplay: Option<bool>,
+    pub shuffle: Option<Shuffle>,
+}
+
+/// spawns mpv & returns as soon as it's running,
+/// the progress is reported through the `playback-*` events.
 #[command]
 pub async fn play_video(
     handle: AppHandle,
     main_folder: OsFolder,
     mut os_videos: Vec<OsVideo>,
-    video: OsVideo,
-    mut user: User,
-) {
-    let instant = time::Instant::now();
-    (|| -> Result<(), MpvError> {
-        let webv_window = match handle.get_webview_window("main") {
-            Some(win) => win,
-            None => return Err(MpvError::WebviewWindowNotFound(String::from("main"))),
-        };
-        let last_url = webv_window.url()?;
-        let last_url = format!("{}#video_title={}", last_url.as_str(), video.title);
+    mut video: OsVideo,
+    user: User,
+    sort_type: Option<String>,
+    bookmark: Option<Bookmark>,
+    shuffle: Option<Shuffle>,
+) -> Result<(), MpvError> {
+    if let Some(sort_type) = sort_type {
+        os_videos.par_sort_by(SortType::from_str(&sort_type)?.sort());
+    }
 
-        webv_window.close()?;
-        let parent_path = match Path::new(&video.path).parent() {
-            Some(pp) => pp,
-            None => return Err(MpvError::InvalidPathName(video.path)),
+    let mut options = LaunchOptions::default();
+    if let Some(mut shuffle) = shuffle {
+        if shuffle.mode == ShuffleMode::FolderTree {
+            os_videos = get_os_videos_in_tree(&handle, &main_folder.path)?;
+        }
+        let resuming = shuffle.seed.is_some();
+        shuffle.apply(&mut os_videos);
+
+        // a new shuffle starts at its first video,
+        // a resumed one where it left off
+        let in_order = os_videos.iter().find(|vid| vid.path == video.path);
+        video = match in_order {
+            Some(vid) if resuming => vid.clone(),
+            _ => os_videos
+                .first()
+                .cloned()
+                .ok_or_else(|| MpvError::OsVideoNotFound(main_folder.path.clone()))?,
         };
+        options.autoplay = Some(true);
+        options.shuffle = Some(shuffle);
+    }
 
-        let video_index = find_video_index(parent_path, video.path.clone())?;
-        let mpvshelf_plugins = handle
-            .path()
-            .resolve("resources/mpvshelf.lua", BaseDirectory::Resource)?;
-
-        let mut args = if user.settings.mpv_settings.autoplay {
-            vec![
-                format!("--playlist-start={video_index}"),
-                format!("--playlist={}", parent_path.to_string_lossy()),
-            ]
-        } else {
-            vec![video.path]
-        };
+    options.start = match &bookmark {
+        Some(bookmark) => Some(bookmark.timestamp),
+        None => start_position(&handle, &main_folder, &video, &user.settings.mpv_settings),
+    };
 
-        args.extend([
-            format!("--script={}", mpvshelf_plugins.to_string_lossy()),
-            format!("--title={} | mpvshelf", main_folder.title),
-        ]);
+    launch_mpv(handle, main_folder, os_videos, video, user, options)
+}
 
-        let status = spawn_mpv(&args, user.settings.mpv_settings.exe_path.as_deref())?;
-        println!(
-            "it took {:.2}ms until mpv was spawned",
-            instant.elapsed().as_millis()
-        );
-        let output = status.wait_with_output()?;
-        let parsed_stdout = parse_mpv_stdout(output.stdout.clone())?;
-
-        //println!("{:#?}", data);
-        let mut main_folder_clone = main_folder.clone();
-        let mut last_watched_video = main_folder.last_watched_video.clone(); // Track the last updated video
-
-        for entry in &parsed_stdout {
-            let video_path = &entry.last_video_path;
-
-            // Iterate through all videos in `os_videos`
-            // and update those that match the path
-            for vid in &mut os_videos.iter_mut() {
-                if vid.path == *video_path {
-                    vid.watched = true;
-                    vid.position = entry.last_video_position;
-                    vid.duration = entry.last_video_duration;
-
-                    // track this as the last watched video
-                    last_watched_video = Some(vid.clone());
-                }
-            }
+/// plays `video` with the rest of `os_videos` as its playlist
+pub fn launch_mpv(
+    handle: AppHandle,
+    main_folder: OsFolder,
+    os_videos: Vec<OsVideo>,
+    video: OsVideo,
+    user: User,
+    options: LaunchOptions,
+) -> Result<(), MpvError> {
+    let LaunchOptions {
+        start,
+        autoplay,
+        shuffle,
+    } = options;
+    let instant = time::Instant::now();
+    // fail right away, instead of once mpv has exited
+    if !Path::new(&video.path).exists() {
+        return Err(MpvError::FileNotFound(video.path));
+    }
+
+    let info = PlaybackSessionInfo {
+        main_folder_path: main_folder.path.clone(),
+        position: start.unwrap_or(0),
+        duration: video.duration,
+        video: Some(video.clone()),
+        shuffle,
+        chapter: None,
+    };
+    let state = handle.state::<PlaybackState>();
+    state.reserve(info.clone())?;
+    let spawned = spawn_player(
+        &handle,
+        &main_folder,
+        &os_videos,
+        &video,
+        &user,
+        start,
+        autoplay,
+    );
+    let player = match spawned {
+        Ok(player) => player,
+        Err(e) => {
+            state.release();
+            return Err(e);
         }
+    };
+    println!(
+        "it took {:.2}ms until the player was spawned",
+        instant.elapsed().as_millis()
+    );
+
+    let tracker = PlaybackTracker::new(handle.clone(), main_folder, os_videos, user, shuffle);
+    start_session(handle, player, tracker, info);
+    Ok(())
+}
 
-        // Assign the tracked last watched video (if any)
-        main_folder_clone.last_watched_video = last_watched_video.clone();
-        user.last_watched_video = last_watched_video;
+fn spawn_player(
+    handle: &AppHandle,
+    main_folder: &OsFolder,
+    os_videos: &[OsVideo],
+    video: &OsVideo,
+    user: &User,
+    start: Option<u64>,
+    autoplay: Option<bool>,
+) -> Result<Box<dyn PlayerSession>, MpvError> {
+    let mpv_settings = &user.settings.mpv_settings;
+    mpv_settings.validate()?;
+    let app_data_dir = handle.path().app_data_dir()?;
+    let mpvshelf_plugins = handle
+        .path()
+        .resolve("resources/mpvshelf.lua", BaseDirectory::Resource)?;
+
+    let profile = resolve_playback_profile(handle, main_folder)?;
+    let autoplay = autoplay
+        .or(profile.autoplay)
+        .unwrap_or(mpv_settings.autoplay);
+
+    let (playlist, start_index) = if autoplay {
+        let video_index = os_videos
+            .iter()
+            .position(|vid| vid.path == video.path)
+            .ok_or_else(|| MpvError::OsVideoNotFound(video.path.clone()))?;
+        (os_videos.to_vec(), video_index)
+    } else {
+        (vec![video.clone()], 0)
+    };
+
+    let mut args = vec![
+        format!(
+            "--config-dir={}",
+            mpv_settings.config_dir(&app_data_dir).to_string_lossy()
+        ),
+        format!("--script={}", mpvshelf_plugins.to_string_lossy()),
+        // the database is the source of truth for positions, not mpv's watch_later
+        String::from("--resume-playback=no"),
+    ];
+    args.extend(plugin_args(
+        handle,
+        &mpv_settings.plugins_dir(&app_data_dir),
+    )?);
+    for sub in &video.subtitles {
+        args.push(format!("--sub-file={}", sub.path));
+    }
+    args.extend(profile.to_mpv_args());
+    if let Some(speed) = audiobook_speed(main_folder, video) {
+        args.push(format!("--speed={speed}"));
+    }
+    // the last picked tracks are more specific than the profile
+    let folder_tracks = get_os_folder_by_path(handle.clone(), main_folder.path.clone())
+        .ok()
+        .and_then(|folder| folder.tracks);
+    if let Some(tracks) = folder_tracks {
+        args.extend(tracks.default_args());
+    }
 
-        // Continue with the update
-        update_os_videos(handle.clone(), os_videos)?;
-        update_os_folders(handle.clone(), vec![main_folder_clone])?;
-        update_user(user, handle.clone())?;
+    let request = PlayRequest {
+        playlist,
+        start_index,
+        start,
+        title: main_folder.title.clone(),
+        mpv_args: args,
+    };
+    let backend = player_backend(&mpv_settings.backend, mpv_settings.exe_path.as_deref());
+    backend.spawn(&request)
+}
 
-        build_window(handle, Some(last_url.as_str()))?;
+/// the start of the chapter playing at `position`;
+/// every file of an audiobook without chapters counts as one.
+fn chapter_start(main_folder: &OsFolder, video: &OsVideo, position: u64) -> Option<u64> {
+    match video.chapter_at(position) {
+        Some(index) => Some(video.chapters[index].start).filter(|start| *start > 0),
+        None if main_folder.audiobook => None,
+        None => Some(position),
+    }
+}
 
-        Ok(())
-    })()
-    .unwrap();
+/// the speed the book was last listened at,
+/// a multi-file book shares it through the folder's last watched file
+fn audiobook_speed(main_folder: &OsFolder, video: &OsVideo) -> Option<f64> {
+    if !main_folder.audiobook {
+        return None;
+    }
+    video.speed.or_else(|| {
+        main_folder
+            .last_watched_video
+            .as_ref()
+            .and_then(|vid| vid.speed)
+    })
 }
 
-pub fn spawn_mpv(args: &[String], mpv_path: Option<&str>) -> Result<Child, MpvError> {
-    let mpv_exe = mpv_path.unwrap_or("mpv");
+/// writes `os_videos` to an m3u playlist in the temp dir,
+/// so mpv plays exactly what the user sees, in the same order.
+/// the session removes it once its player has exited.
+pub fn write_playlist(session_id: &str, os_videos: &[OsVideo]) -> Result<PathBuf, io::Error> {
+    let playlist_path = env::temp_dir().join(format!("mpvshelf-{session_id}.m3u"));
+    let mut playlist = String::from("#EXTM3U\n");
+    for vid in os_videos {
+        playlist.push_str(&vid.path);
+        playlist.push('\n');
+    }
+    fs::write(&playlist_path, playlist)?;
 
-    let child = Command::new(mpv_exe)
-        .args(args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
+    Ok(playlist_path)
+}
 
-    Ok(child)
+/// where `video` starts playing when it isn't played from a bookmark
+pub fn start_position(
+    handle: &AppHandle,
+    main_folder: &OsFolder,
+    video: &OsVideo,
+    mpv_settings: &MpvSettings,
+) -> Option<u64> {
+    let start = resume_position(handle, video, mpv_settings)?;
+    if mpv_settings.resume_chapter_start {
+        chapter_start(main_folder, video, start)
+    } else {
+        Some(start)
+    }
 }
 
-fn parse_mpv_stdout(stdout: Vec<u8>) -> Result<Vec<MpvPlaybackData>, MpvStdoutError> {
-    let output_str = String::from_utf8(stdout)?;
+/// returns where `video` should start playing from,
+/// according to the user's `ResumeMode`
+fn resume_position(handle: &AppHandle, video: &OsVideo, mpv_settings: &MpvSettings) -> Option<u64> {
+    if video.position == 0 {
+        return None;
+    }
+    if video.duration > 0 && video.position + mpv_settings.resume_tail >= video.duration {
+        return None;
+    }
 
-    // split output by "Playing" indicates the start of each new part
-    let parts: Vec<String> = output_str
-        .split("Playing")
-        .skip(1) // skip the first part before the first "Playing"
-        .map(|part| format!("Playing{}", part.trim())) // re-add the "Playing" to each part
-        .collect();
+    match mpv_settings.resume {
+        ResumeMode::Always => Some(video.position),
+        ResumeMode::Never => None,
+        ResumeMode::Ask => handle
+            .dialog()
+            .message(format!(
+                "Resume {} from {}?",
+                video.title,
+                format_timestamp(video.position)
+            ))
+            .title("mpvshelf")
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                String::from("Resume"),
+                String::from("Start Over"),
+            ))
+            .blocking_show()
+            .then_some(video.position),
+    }
+}
 
-    let data: Result<Vec<MpvPlaybackData>, MpvStdoutError> = parts
-        .into_par_iter()
-        .map(|part| handle_mpv_stdout_section(part))
-        .collect();
+/// `mpvshelf.lua` binds `Q` to `quit-watch-later` with this exit code,
+/// mpv's own codes only go up to 4.
+pub const QUIT_WATCH_LATER_EXIT_CODE: i32 = 5;
+
+/// some files of the playlist failed to load, but the rest played
+const SOME_ERRORS_EXIT_CODE: i32 = 3;
+
+/// mpv catches SIGTERM, SIGINT & SIGHUP & quits with this code,
+/// without saying which signal it was
+const SIGNAL_EXIT_CODE: i32 = 4;
+
+/// turns how mpv exited into an error, using the errors it wrote to stderr.
+/// https://mpv.io/manual/master/#exit-codes
+pub fn classify_mpv_exit(status: ExitStatus, stderr: &str) -> Result<(), MpvError> {
+    let code = match status.code() {
+        Some(0) => return Ok(()),
+        Some(QUIT_WATCH_LATER_EXIT_CODE) => return Err(MpvError::QuitWatchLater),
+        Some(SOME_ERRORS_EXIT_CODE) => {
+            eprintln!("mpv failed to load some of the playlist:\n{stderr}");
+            return Ok(());
+        }
+        Some(SIGNAL_EXIT_CODE) => return Err(MpvError::KilledBySignal(0)),
+        Some(code) => code,
+        None => return Err(MpvError::KilledBySignal(exit_signal(&status))),
+    };
+
+    for line in stderr.lines().map(str::trim) {
+        // [file] Cannot open file '/videos/01.mkv': No such file or directory
+        // scripts & config includes that are missing report the same os error,
+        // so only mpv failing to open the video counts.
+        if line.contains("Cannot open file '") {
+            let path = line.split('\'').nth(1).unwrap_or(line);
+            return Err(MpvError::FileNotFound(path.to_string()));
+        }
+        // [vd] Failed to initialize a decoder for codec 'av1'.
+        if line.contains("Failed to initialize a decoder")
+            || line.contains("Could not find decoder")
+        {
+            let codec = line.split('\'').nth(1).unwrap_or(line);
+            return Err(MpvError::UnsupportedCodec(codec.to_string()));
+        }
+        // Error parsing option foo (option not found)
+        // Setting commandline option --foo=bar failed.
+        if line.starts_with("Error parsing")
+            || line.contains("Error loading config file")
+            || (line.starts_with("Setting commandline option") && line.ends_with("failed."))
+        {
+            return Err(MpvError::ConfigError(line.to_string()));
+        }
+    }
 
-    data
+    // the last few lines are usually the ones explaining the exit
+    let lines: Vec<&str> = stderr.lines().collect();
+    let tail = lines[lines.len().saturating_sub(5)..].join("\n");
+    Err(MpvError::ExitCode(code, tail))
 }
 
-fn handle_mpv_stdout_section(sect: String) -> Result<MpvPlaybackData, MpvStdoutError> {
-    //println!("\n{sect}\n");
-    let mut data = MpvPlaybackData::new();
-    let mut found_path = false;
-    let mut found_timestamp = false;
-
-    // First find the path since it's at the start
-    for line in sect.lines() {
-        if let Some(path) = line.strip_prefix("Playing: ") {
-            data.last_video_path = normalize_path(path).to_string_lossy().to_string();
-            found_path = true;
-            break;
+#[cfg(unix)]
+fn exit_signal(status: &ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal().unwrap_or(0)
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &ExitStatus) -> i32 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(path: &str, watched: bool) -> OsVideo {
+        OsVideo {
+            watched,
+            ..OsVideo::stub("/shows", path)
         }
     }
 
-    // find last actual timestamp (going backwards)
-    for line in sect.lines().rev() {
-        if line.starts_with("Exiting...") || line.starts_with("Saving state.") {
-            continue; // skip these lines they might come after the final timestamp
-        }
-        if let Some(whole_duration) = line.strip_prefix("AV: ") {
-            let parts: Vec<&str> = whole_duration.split('/').map(str::trim).collect();
-            if parts.len() >= 2 {
-                if let Some((duration, _)) = parts[1].split_once("(") {
-                    data.update_timestamp(TimestampType::Position, parts[0].to_string())?;
-                    data.update_timestamp(TimestampType::Duration, duration.trim().to_string())?;
-                    found_timestamp = true;
-                    break; // stop after finding the first (last) valid timestamp
-                }
-            }
-        }
+    fn shuffled(mode: ShuffleMode, mut os_videos: Vec<OsVideo>) -> Vec<String> {
+        let mut shuffle = Shuffle {
+            mode,
+            seed: Some(42),
+        };
+        shuffle.apply(&mut os_videos);
+        os_videos.into_iter().map(|vid| vid.path).collect()
     }
 
-    if !found_path {
-        return Err(MpvStdoutError::MissingVideoTitle(sect));
+    #[test]
+    fn shuffle_keeps_its_order_as_videos_are_watched_or_added() {
+        let paths: Vec<String> = (1..=12).map(|ep| format!("/shows/{ep:02}.mkv")).collect();
+        let all = shuffled(
+            ShuffleMode::All,
+            paths.iter().rev().map(|path| video(path, false)).collect(),
+        );
+        assert_ne!(all, paths);
+
+        // watching a video only takes it out of the order
+        let watched = ["/shows/03.mkv", "/shows/07.mkv"];
+        let unwatched = shuffled(
+            ShuffleMode::Unwatched,
+            paths
+                .iter()
+                .map(|path| video(path, watched.contains(&path.as_str())))
+                .collect(),
+        );
+        let mut expected = all.clone();
+        expected.retain(|path| !watched.contains(&path.as_str()));
+        assert_eq!(unwatched, expected);
+
+        // a new video in the tree goes somewhere, without moving the rest
+        let mut grown = paths.clone();
+        grown.push("/shows/13.mkv".into());
+        let mut tree = shuffled(
+            ShuffleMode::FolderTree,
+            grown.iter().map(|path| video(path, false)).collect(),
+        );
+        tree.retain(|path| path != "/shows/13.mkv");
+        assert_eq!(tree, all);
     }
 
-    // handle the edge case where path is found but timestamp isn't
-    // this only seems to happen when you finish watching a video that has a timestamp-
-    // and then exit mpv on the video that plays after
-    if !found_timestamp {
-        // Set default values for already watched videos
-        data.update_timestamp(TimestampType::Position, "00:00:00".to_string())?;
-        data.update_timestamp(TimestampType::Duration, "00:00:00".to_string())?;
-        println!(
-            "Warning: No timestamp found for '{}', using default values.",
-            data.last_video_path
+    fn parsed_version(stdout: &str) -> Option<MpvVersion> {
+        version_line(stdout, "mpv ")
+            .as_deref()
+            .and_then(MpvVersion::parse)
+    }
+
+    #[test]
+    fn parses_the_version_of_release_git_and_distro_builds() {
+        let release = "\
+mpv v0.38.0 Copyright © 2000-2024 mpv/MPlayer/mplayer2 projects
+ built on Jun  6 2024 13:44:05
+libplacebo version: v6.338.2
+FFmpeg version: n7.0.1
+FFmpeg library versions:
+   libavutil       59.8.100
+";
+        let version = parsed_version(release).unwrap();
+        assert_eq!(version.to_string(), "0.38.0");
+        assert_eq!(
+            version_line(release, "libplacebo version:").unwrap(),
+            "v6.338.2"
+        );
+        assert_eq!(version_line(release, "FFmpeg version:").unwrap(), "n7.0.1");
+
+        let git =
+            "mpv v0.38.0-451-g1a2b3c4d5 Copyright © 2000-2024 mpv/MPlayer/mplayer2 projects\n";
+        assert_eq!(parsed_version(git).unwrap().to_string(), "0.38.0");
+
+        let ubuntu = "\
+mpv 0.34.1 Copyright © 2000-2021 mpv/MPlayer/mplayer2 projects
+ built on UNKNOWN
+FFmpeg library versions:
+";
+        assert_eq!(parsed_version(ubuntu).unwrap().to_string(), "0.34.1");
+
+        assert_eq!(MpvVersion::parse("0.35").unwrap().to_string(), "0.35.0");
+        assert_eq!(
+            MpvVersion::parse("0.34.1+git").unwrap().to_string(),
+            "0.34.1"
         );
     }
 
-    Ok(data)
+    #[test]
+    fn unparsable_versions_are_unknown() {
+        assert_eq!(parsed_version("mpv UNKNOWN Copyright © 2000-2024\n"), None);
+        assert_eq!(MpvVersion::parse("git-2024-06-06"), None);
+        assert_eq!(MpvVersion::parse("0"), None);
+    }
+
+    #[test]
+    fn old_versions_sort_below_the_minimum() {
+        let focal =
+            parsed_version("mpv 0.32.0 Copyright © 2000-2020 mpv/MPlayer/mplayer2 projects\n");
+        assert!(focal.unwrap() < MIN_MPV_VERSION);
+        assert!(MpvVersion::parse("0.33.0").unwrap() >= MIN_MPV_VERSION);
+        assert!(MpvVersion::parse("1.0.0").unwrap() > MpvVersion::parse("0.40.2").unwrap());
+    }
+
+    #[cfg(unix)]
+    fn exited(code: i32) -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[cfg(windows)]
+    fn exited(code: i32) -> ExitStatus {
+        use std::os::windows::process::ExitStatusExt;
+        ExitStatus::from_raw(code as u32)
+    }
+
+    #[test]
+    fn classifies_mpv_exits_from_stderr() {
+        assert!(classify_mpv_exit(exited(0), "").is_ok());
+        assert!(matches!(
+            classify_mpv_exit(exited(QUIT_WATCH_LATER_EXIT_CODE), ""),
+            Err(MpvError::QuitWatchLater)
+        ));
+
+        let missing_video = "\
+[file] Cannot open file '/videos/Show S01/01.mkv': No such file or directory
+Failed to open /videos/Show S01/01.mkv.
+
+Exiting... (Errors when loading file)
+";
+        assert!(matches!(
+            classify_mpv_exit(exited(2), missing_video),
+            Err(MpvError::FileNotFound(path)) if path == "/videos/Show S01/01.mkv"
+        ));
+
+        // the playlist went on without the missing episode
+        let some_errors = "\
+[file] Cannot open file '/videos/Show S01/02.mkv': No such file or directory
+Failed to open /videos/Show S01/02.mkv.
+
+Exiting... (Some errors happened)
+";
+        assert!(classify_mpv_exit(exited(3), some_errors).is_ok());
+
+        // SIGTERM, SIGINT & SIGHUP are caught by mpv
+        assert!(matches!(
+            classify_mpv_exit(exited(4), "\nExiting... (Quit)\n"),
+            Err(MpvError::KilledBySignal(_))
+        ));
+
+        let missing_codec = "\
+[vd] Failed to initialize a decoder for codec 'av1'.
+Video: no video
+
+Exiting... (Errors when loading file)
+";
+        assert!(matches!(
+            classify_mpv_exit(exited(2), missing_codec),
+            Err(MpvError::UnsupportedCodec(codec)) if codec == "av1"
+        ));
+
+        let unknown_option = "\
+Error parsing option sub-autoo (option not found)
+Setting commandline option --sub-autoo=fuzzy failed.
+
+Exiting... (Fatal error)
+";
+        assert!(matches!(
+            classify_mpv_exit(exited(1), unknown_option),
+            Err(MpvError::ConfigError(line)) if line.contains("sub-autoo")
+        ));
+    }
+
+    #[test]
+    fn missing_scripts_are_not_a_missing_video() {
+        let missing_script = "\
+[uosc] Lua error: cannot open /home/user/.config/mpv/scripts/uosc/lib/std.lua: No such file or directory
+[cplayer] Error loading script /home/user/.config/mpv/scripts/uosc/main.lua
+
+Exiting... (Fatal error)
+";
+        assert!(matches!(
+            classify_mpv_exit(exited(1), missing_script),
+            Err(MpvError::ExitCode(1, tail)) if tail.contains("uosc/lib/std.lua")
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_killed_mpv_reports_its_signal() {
+        use std::os::unix::process::ExitStatusExt;
+        assert!(matches!(
+            classify_mpv_exit(ExitStatus::from_raw(9), ""),
+            Err(MpvError::KilledBySignal(9))
+        ));
+    }
 }
@@ -0,0 +1,1066 @@
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{command, AppHandle, Emitter, Manager, Runtime, Wry};
+
+use crate::database::data::v2::{OsFolder, OsVideo, Track, TrackSelection, User};
+use crate::database::{add_bookmark, get_os_folder_by_path, save_playback_progress};
+use crate::error::{DatabaseError, MpvError};
+use crate::fs::normalize_path;
+use crate::ipc::MpvEvent;
+use crate::mpv::{MpvPlaybackData, Shuffle};
+use crate::player::{PlayerSession, PlayerStopper};
+
+///! Tracks what mpv is currently playing,
+///! and checkpoints the progress to the database while it's still running.
+
+/// The mpv instance mpvshelf is currently playing with, if any.
+/// Only one session runs at a time.
+#[derive(Default)]
+pub struct PlaybackState(Mutex<Option<PlaybackSession>>);
+
+pub struct PlaybackSession {
+    info: PlaybackSessionInfo,
+    /// `None` while the player is still spawning
+    stopper: Option<Box<dyn PlayerStopper>>,
+    sleep_timer: Option<ActiveSleepTimer>,
+}
+
+/// when the sleep timer stops playback
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepTimer {
+    Minutes(u64),
+    EndOfEpisode,
+    /// counting the current episode
+    Episodes(u32),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepAction {
+    Pause,
+    Quit,
+}
+
+struct ActiveSleepTimer {
+    action: SleepAction,
+    deadline: Option<Instant>,
+    /// including the current one
+    episodes_left: u32,
+}
+
+/// what the sleep timer wants done with mpv
+enum SleepStep {
+    /// the timer ran out mid-episode
+    StopNow(SleepAction),
+    /// the current episode is the last one
+    StopAfterFile(SleepAction),
+}
+
+/// Snapshot of the running session, as sent with the `playback-*` events.
+#[derive(Serialize, Clone, Debug)]
+pub struct PlaybackSessionInfo {
+    pub main_folder_path: String,
+    pub video: Option<OsVideo>,
+    /// * in seconds.
+    pub position: u64,
+    pub duration: u64,
+    /// pass this back to `play_video` to resume the same order
+    pub shuffle: Option<Shuffle>,
+    /// only for videos with chapters & audiobooks
+    pub chapter: Option<ChapterProgress>,
+}
+
+/// "chapter `index` of `count`"
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ChapterProgress {
+    /// 1-based
+    pub index: usize,
+    pub count: usize,
+    pub title: Option<String>,
+}
+
+impl PlaybackState {
+    pub fn info(&self) -> Option<PlaybackSessionInfo> {
+        let session = self.0.lock().unwrap();
+        session.as_ref().map(|s| s.info.clone())
+    }
+
+    /// Claims the slot for a new session before its player spawns,
+    /// so two sessions can't start at the same time.
+    pub fn reserve(&self, info: PlaybackSessionInfo) -> Result<(), MpvError> {
+        let mut session = self.0.lock().unwrap();
+        if session.is_some() {
+            let path = info.video.map(|vid| vid.path).unwrap_or_default();
+            return Err(MpvError::PlaybackSessionRunning(path));
+        }
+        *session = Some(PlaybackSession {
+            info,
+            stopper: None,
+            sleep_timer: None,
+        });
+        Ok(())
+    }
+
+    /// frees the slot once the player has exited, or failed to spawn
+    pub fn release(&self) {
+        self.0.lock().unwrap().take();
+    }
+
+    fn set_info(&self, info: PlaybackSessionInfo) {
+        if let Some(session) = self.0.lock().unwrap().as_mut() {
+            session.info = info;
+        }
+    }
+
+    /// advances the sleep timer with the latest event;
+    /// the timer is done once it returns a step.
+    fn sleep_timer_step(&self, event: &MpvEvent, loaded: bool) -> Option<SleepStep> {
+        let mut session = self.0.lock().unwrap();
+        let session = session.as_mut()?;
+        let timer = session.sleep_timer.as_mut()?;
+
+        let step = match timer.deadline {
+            Some(deadline) => {
+                (Instant::now() >= deadline).then_some(SleepStep::StopNow(timer.action))
+            }
+            None => {
+                if *event == MpvEvent::EndFile && timer.episodes_left > 1 {
+                    timer.episodes_left -= 1;
+                }
+                // mpv is in between files until the next one has loaded
+                (loaded && timer.episodes_left <= 1)
+                    .then_some(SleepStep::StopAfterFile(timer.action))
+            }
+        };
+        if step.is_some() {
+            session.sleep_timer = None;
+        }
+        step
+    }
+}
+
+pub struct PlaybackTracker<R: Runtime = Wry> {
+    handle: AppHandle<R>,
+    user_id: String,
+    main_folder_path: String,
+    /// every folder the videos in this session belong to, keyed by path
+    folders: HashMap<String, OsFolder>,
+    os_videos: Vec<OsVideo>,
+    current: Option<MpvPlaybackData>,
+    /// reported before the `path` of the entry it belongs to
+    next_playlist_pos: Option<i64>,
+    shuffle: Option<Shuffle>,
+    /// mpv keeps the speed across files
+    speed: Option<f64>,
+    /// mpv resets the track properties while it switches files,
+    /// so track changes only count in between `FileLoaded` & `EndFile`
+    loaded: bool,
+    checkpoint_interval: Duration,
+    last_checkpoint: Instant,
+}
+
+impl<R: Runtime> PlaybackTracker<R> {
+    pub fn new(
+        handle: AppHandle<R>,
+        main_folder: OsFolder,
+        os_videos: Vec<OsVideo>,
+        user: User,
+        shuffle: Option<Shuffle>,
+    ) -> Self {
+        let checkpoint_interval =
+            Duration::from_secs(user.settings.mpv_settings.checkpoint_interval);
+        let main_folder_path = main_folder.path.clone();
+        let folders = HashMap::from([(main_folder.path.clone(), main_folder)]);
+
+        Self {
+            handle,
+            user_id: user.id,
+            main_folder_path,
+            folders,
+            os_videos,
+            current: None,
+            next_playlist_pos: None,
+            shuffle,
+            speed: None,
+            loaded: false,
+            checkpoint_interval,
+            last_checkpoint: Instant::now(),
+        }
+    }
+
+    pub fn current_video(&self) -> Option<&OsVideo> {
+        let data = self.current.as_ref()?;
+        self.os_videos
+            .iter()
+            .find(|vid| vid.path == data.last_video_path)
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    pub fn current_folder(&self) -> Option<&OsFolder> {
+        self.folders.get(&self.current_video()?.main_folder_path)
+    }
+
+    /// the chapter of the current video, or for audiobooks
+    /// without chapters the file's place in the book
+    fn chapter_progress(&self, position: u64) -> Option<ChapterProgress> {
+        let vid = self.current_video()?;
+        if let Some(index) = vid.chapter_at(position) {
+            return Some(ChapterProgress {
+                index: index + 1,
+                count: vid.chapters.len(),
+                title: vid.chapters[index].title.clone(),
+            });
+        }
+
+        if !self.current_folder()?.audiobook {
+            return None;
+        }
+        let book: Vec<&OsVideo> = self
+            .os_videos
+            .iter()
+            .filter(|book_vid| book_vid.main_folder_path == vid.main_folder_path)
+            .collect();
+        let index = book.iter().position(|book_vid| book_vid.path == vid.path)?;
+        Some(ChapterProgress {
+            index: index + 1,
+            count: book.len(),
+            title: Some(vid.title.clone()),
+        })
+    }
+
+    pub fn info(&self) -> PlaybackSessionInfo {
+        let position = self.current.as_ref().map_or(0, |d| d.last_video_position);
+        PlaybackSessionInfo {
+            main_folder_path: self.main_folder_path.clone(),
+            video: self.current_video().cloned(),
+            position,
+            duration: self.current.as_ref().map_or(0, |d| d.last_video_duration),
+            shuffle: self.shuffle,
+            chapter: self.chapter_progress(position),
+        }
+    }
+
+    /// feeds an event from mpv into the session;
+    /// checkpoints on every file change & once every `checkpoint_interval`
+    pub fn handle_event(&mut self, event: MpvEvent) -> Result<(), DatabaseError> {
+        match event {
+            // the end of the playlist is saved by its `end-file`,
+            // & the last video stays around for `playback-ended`
+            MpvEvent::PlaylistPos(pos) if pos < 0 => {}
+            MpvEvent::PlaylistPos(pos) => match self.current.as_mut() {
+                // the new file's `path` came first
+                Some(data) if data.playlist_pos.is_none() => data.playlist_pos = Some(pos),
+                // save the entry mpv is leaving before anything of the next one arrives,
+                // even when it's the same file again
+                Some(data) if data.playlist_pos != Some(pos) => {
+                    self.checkpoint()?;
+                    self.current = None;
+                    self.next_playlist_pos = Some(pos);
+                }
+                Some(_) => {}
+                None => self.next_playlist_pos = Some(pos),
+            },
+            MpvEvent::Path(path) => {
+                // save the file mpv is switching away from first
+                self.checkpoint()?;
+                let mut data = MpvPlaybackData::new();
+                data.last_video_path = normalize_path(&path).to_string_lossy().to_string();
+                data.playlist_pos = self.next_playlist_pos.take();
+                self.current = Some(data);
+                self.checkpoint()?;
+            }
+            MpvEvent::TimePos(pos) => {
+                if let Some(data) = self.current.as_mut() {
+                    data.last_video_position = pos as u64;
+                }
+                if self.last_checkpoint.elapsed() >= self.checkpoint_interval {
+                    self.checkpoint()?;
+                }
+            }
+            MpvEvent::Duration(dur) => {
+                if let Some(data) = self.current.as_mut() {
+                    data.last_video_duration = dur as u64;
+                }
+            }
+            MpvEvent::Aid(track) => self.update_tracks(|tracks| tracks.aid = Some(track)),
+            MpvEvent::Sid(track) => self.update_tracks(|tracks| tracks.sid = Some(track)),
+            MpvEvent::AudioLang(lang) => self.update_tracks(|tracks| tracks.audio_lang = lang),
+            MpvEvent::SubLang(lang) => self.update_tracks(|tracks| tracks.sub_lang = lang),
+            MpvEvent::Speed(speed) => self.speed = Some(speed),
+            MpvEvent::FileLoaded => self.loaded = true,
+            MpvEvent::EndFile => {
+                self.loaded = false;
+                // the next file picks its tracks from the folder's defaults
+                self.checkpoint()?;
+            }
+            MpvEvent::ClientMessage(args) => self.handle_client_message(&args)?,
+            MpvEvent::Shutdown => {}
+        }
+
+        Ok(())
+    }
+
+    /// messages sent by `mpvshelf.lua`
+    fn handle_client_message(&mut self, args: &[String]) -> Result<(), DatabaseError> {
+        match args {
+            [name, path, timestamp] if name == "mpvshelf-bookmark" => {
+                let Ok(timestamp) = timestamp.parse::<f64>() else {
+                    return Ok(());
+                };
+                let video_path = normalize_path(path).to_string_lossy().to_string();
+                let bookmark = add_bookmark(
+                    self.handle.clone(),
+                    video_path,
+                    timestamp as u64,
+                    None,
+                    None,
+                )?;
+                self.handle.emit("bookmark-added", bookmark)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn update_tracks(&mut self, f: impl FnOnce(&mut TrackSelection)) {
+        if !self.loaded {
+            return;
+        }
+        if let Some(data) = self.current.as_mut() {
+            f(&mut data.tracks);
+        }
+    }
+
+    /// writes the current video's progress,
+    /// and sets it as the folder's & user's last watched video.
+    pub fn checkpoint(&mut self) -> Result<(), DatabaseError> {
+        self.last_checkpoint = Instant::now();
+
+        let Some(data) = &self.current else {
+            return Ok(());
+        };
+        // a queue's videos come from any number of folders
+        let main_folder_path = self
+            .os_videos
+            .iter()
+            .find(|vid| vid.path == data.last_video_path)
+            .map(|vid| vid.main_folder_path.clone());
+        let Some(main_folder_path) = main_folder_path else {
+            return Ok(());
+        };
+        if !self.folders.contains_key(&main_folder_path) {
+            if let Ok(folder) = get_os_folder_by_path(self.handle.clone(), main_folder_path.clone())
+            {
+                self.folders.insert(main_folder_path, folder);
+            }
+        }
+
+        let Some(saved) = save_playback_progress(&self.handle, &self.user_id, data, self.speed)?
+        else {
+            return Ok(());
+        };
+        if let Some(vid) = self.os_videos.iter_mut().find(|vid| vid.path == saved.path) {
+            *vid = saved;
+        }
+
+        Ok(())
+    }
+}
+
+/// Takes ownership of a freshly spawned player in the slot `PlaybackState::reserve` claimed,
+/// and follows it on a background thread until it exits.
+///
+/// Emits `playback-started`, `playback-progress`, `playback-ended` & `playback-error`.
+pub fn start_session(
+    handle: AppHandle,
+    mut player: Box<dyn PlayerSession>,
+    tracker: PlaybackTracker,
+    info: PlaybackSessionInfo,
+) {
+    if let Some(session) = handle.state::<PlaybackState>().0.lock().unwrap().as_mut() {
+        session.info = info.clone();
+        session.stopper = Some(player.stopper());
+    }
+    if let Err(e) = handle.emit("playback-started", &info) {
+        eprintln!("failed to emit the start of the playback session: {e}");
+    }
+
+    thread::spawn(move || {
+        let result = follow_session(&handle, player.as_mut(), tracker);
+
+        // the player never connected, so nothing would ever stop it
+        let exit = player.wait(matches!(result, Err(MpvError::Ipc(_))));
+        // `stop_playback` can reach the player until it has exited
+        handle.state::<PlaybackState>().release();
+
+        let emitted = match session_result(result, exit) {
+            Ok(info) => handle.emit("playback-ended", info),
+            Err(e) => handle.emit("playback-error", e.to_string()),
+        };
+        if let Err(e) = emitted {
+            eprintln!("failed to emit the end of the playback session: {e}");
+        }
+    });
+}
+
+/// combines how following the player went with how it exited
+fn session_result(
+    followed: Result<PlaybackSessionInfo, MpvError>,
+    exit: Result<(), MpvError>,
+) -> Result<PlaybackSessionInfo, MpvError> {
+    match (followed, exit) {
+        // the position was saved like after any other quit
+        (Ok(info), Ok(()) | Err(MpvError::QuitWatchLater)) => Ok(info),
+        (Ok(_), Err(e)) => Err(e),
+        // mpv's own exit explains an ipc failure better than the ipc error does,
+        // unless it was only killed for it
+        (
+            Err(MpvError::Ipc(e)),
+            Ok(()) | Err(MpvError::QuitWatchLater | MpvError::KilledBySignal(_)),
+        ) => Err(MpvError::Ipc(e)),
+        (Err(MpvError::Ipc(_)), Err(exit_error)) => Err(exit_error),
+        (Err(e), _) => Err(e),
+    }
+}
+
+/// `--start` & `--sub-file` apply to every file in the playlist,
+/// so unset them as soon as the first one has loaded.
+fn reset_first_file_options(player: &mut dyn PlayerSession) -> Result<(), MpvError> {
+    player.command(json!(["set_property", "start", "none"]))?;
+    player.command(json!(["change-list", "sub-files", "clr", ""]))?;
+    Ok(())
+}
+
+/// the first file gets its subtitles through `--sub-file`,
+/// every file after it through ipc.
+fn add_subtitles(player: &mut dyn PlayerSession, vid: &OsVideo) -> Result<(), MpvError> {
+    for sub in &vid.subtitles {
+        let title = Path::new(&sub.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let lang = sub.lang.clone().unwrap_or_default();
+        player.command(json!(["sub-add", sub.path, "auto", title, lang]))?;
+    }
+    Ok(())
+}
+
+/// selects the tracks the video was last played with
+fn restore_tracks(player: &mut dyn PlayerSession, vid: &OsVideo) -> Result<(), MpvError> {
+    let Some(tracks) = &vid.tracks else {
+        return Ok(());
+    };
+    for (name, track) in [("aid", tracks.aid), ("sid", tracks.sid)] {
+        match track {
+            Some(Track::Id(id)) => player.command(json!(["set_property", name, id]))?,
+            Some(Track::Off) => player.command(json!(["set_property", name, "no"]))?,
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+/// mpv keeps the last selected track for the rest of the playlist,
+/// so switch back to picking by the folder's default languages
+/// before the next file loads.
+fn apply_folder_tracks(player: &mut dyn PlayerSession, folder: &OsFolder) -> Result<(), MpvError> {
+    let Some(tracks) = &folder.tracks else {
+        return Ok(());
+    };
+    player.command(json!(["set_property", "aid", "auto"]))?;
+    if let Some(audio_lang) = &tracks.audio_lang {
+        player.command(json!(["set_property", "alang", audio_lang]))?;
+    }
+    if tracks.sid == Some(Track::Off) {
+        player.command(json!(["set_property", "sid", "no"]))?;
+    } else {
+        player.command(json!(["set_property", "sid", "auto"]))?;
+        if let Some(sub_lang) = &tracks.sub_lang {
+            player.command(json!(["set_property", "slang", sub_lang]))?;
+        }
+    }
+    Ok(())
+}
+
+fn apply_sleep_step<R: Runtime>(
+    player: &mut dyn PlayerSession,
+    tracker: &mut PlaybackTracker<R>,
+    step: SleepStep,
+) -> Result<(), MpvError> {
+    match step {
+        SleepStep::StopNow(SleepAction::Pause) => {
+            player.command(json!(["set_property", "pause", true]))?;
+            tracker.checkpoint()?;
+        }
+        // the session checkpoints once mpv shuts down
+        SleepStep::StopNow(SleepAction::Quit) => player.command(json!(["quit"]))?,
+        // mpv quits at the end of the playlist,
+        // or pauses on the last frame with `keep-open`
+        SleepStep::StopAfterFile(action) => {
+            player.command(json!(["playlist-clear"]))?;
+            if action == SleepAction::Pause {
+                player.command(json!(["set_property", "keep-open", "yes"]))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn follow_session<R: Runtime>(
+    handle: &AppHandle<R>,
+    player: &mut dyn PlayerSession,
+    mut tracker: PlaybackTracker<R>,
+) -> Result<PlaybackSessionInfo, MpvError> {
+    let state = handle.state::<PlaybackState>();
+    let mut last_info = tracker.info();
+    let mut first_file = true;
+
+    while let Some(event) = player.next_event()? {
+        if event == MpvEvent::Shutdown {
+            break;
+        }
+        let file_changed = matches!(event, MpvEvent::Path(_));
+        let file_loaded = event == MpvEvent::FileLoaded;
+        let file_ended = event == MpvEvent::EndFile;
+        tracker.handle_event(event.clone())?;
+
+        if file_loaded {
+            if first_file {
+                reset_first_file_options(player)?;
+                first_file = false;
+            } else if let Some(vid) = tracker.current_video() {
+                add_subtitles(player, vid)?;
+            }
+            if let Some(vid) = tracker.current_video() {
+                restore_tracks(player, vid)?;
+            }
+        }
+        if file_ended {
+            if let Some(folder) = tracker.current_folder() {
+                apply_folder_tracks(player, folder)?;
+            }
+        }
+
+        if let Some(step) = state.sleep_timer_step(&event, tracker.is_loaded()) {
+            apply_sleep_step(player, &mut tracker, step)?;
+            handle.emit("sleep-timer-fired", ())?;
+        }
+
+        // time-pos changes many times a second, only report whole seconds
+        let info = tracker.info();
+        if file_changed || info.position != last_info.position {
+            state.set_info(info.clone());
+            handle.emit("playback-progress", &info)?;
+            last_info = info;
+        }
+    }
+
+    tracker.checkpoint()?;
+    Ok(tracker.info())
+}
+
+#[command]
+pub fn get_playback_session(handle: AppHandle) -> Option<PlaybackSessionInfo> {
+    handle.state::<PlaybackState>().info()
+}
+
+/// replaces any running sleep timer
+#[command]
+pub fn set_sleep_timer(
+    handle: AppHandle,
+    timer: SleepTimer,
+    action: SleepAction,
+) -> Result<(), MpvError> {
+    let state = handle.state::<PlaybackState>();
+    let mut session = state.0.lock().unwrap();
+    let Some(session) = session.as_mut() else {
+        return Err(MpvError::NoPlaybackSession);
+    };
+
+    let (deadline, episodes_left) = match timer {
+        SleepTimer::Minutes(mins) => (Some(Instant::now() + Duration::from_secs(mins * 60)), 0),
+        SleepTimer::EndOfEpisode => (None, 1),
+        SleepTimer::Episodes(episodes) => (None, episodes.max(1)),
+    };
+    session.sleep_timer = Some(ActiveSleepTimer {
+        action,
+        deadline,
+        episodes_left,
+    });
+
+    Ok(())
+}
+
+/// the playlist can't be restored once the timer reached the last episode
+#[command]
+pub fn cancel_sleep_timer(handle: AppHandle) -> Result<(), MpvError> {
+    let state = handle.state::<PlaybackState>();
+    let mut session = state.0.lock().unwrap();
+    let Some(session) = session.as_mut() else {
+        return Err(MpvError::NoPlaybackSession);
+    };
+    session.sleep_timer = None;
+
+    Ok(())
+}
+
+#[command]
+pub fn stop_playback(handle: AppHandle) -> Result<(), MpvError> {
+    let state = handle.state::<PlaybackState>();
+    let mut session = state.0.lock().unwrap();
+    let Some(session) = session.as_mut() else {
+        return Err(MpvError::NoPlaybackSession);
+    };
+
+    match session.stopper.as_mut() {
+        Some(stopper) => stopper.stop(),
+        None => Err(MpvError::PlaybackSessionStarting),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::remove_dir_all;
+    use std::path::PathBuf;
+
+    use serde_json::Value;
+    use tauri::test::{mock_app, MockRuntime};
+    use tauri::App;
+
+    use super::*;
+    use crate::database::data::v2::{CompletionPolicy, Settings};
+    use crate::database::{
+        get_os_videos_by_paths, get_user_by_id, init_database, save_user, update_os_folders,
+        update_os_videos,
+    };
+    use crate::error::MpvIpcError;
+    use crate::player::fake::FakeBackend;
+    use crate::player::{PlayRequest, PlayerBackend};
+
+    /// a folder of two 24 minute episodes in a throwaway database
+    struct Library {
+        app: App<MockRuntime>,
+        dir: PathBuf,
+        user: User,
+        folder: OsFolder,
+        videos: Vec<OsVideo>,
+    }
+
+    impl Library {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("mpvshelf-{name}-{}", std::process::id()));
+            let _ = remove_dir_all(&dir);
+            let app = mock_app();
+            init_database(&dir, app.handle()).unwrap();
+            app.manage(PlaybackState::default());
+
+            let user = User {
+                id: "1".into(),
+                username: "default".into(),
+                settings: Settings::default(),
+                last_watched_video: None,
+            };
+            let folder = OsFolder {
+                user_id: "1".into(),
+                path: "/shows/show".into(),
+                title: "show".into(),
+                parent_path: None,
+                last_watched_video: None,
+                cover_img_path: None,
+                credits_offset: None,
+                profile: None,
+                tracks: None,
+                audiobook: false,
+                update_date: String::new(),
+                update_time: String::new(),
+            };
+            let videos: Vec<OsVideo> = ["01.mkv", "02.mkv"]
+                .into_iter()
+                .map(|name| OsVideo {
+                    duration: 1440,
+                    ..OsVideo::stub(&folder.path, &format!("{}/{name}", folder.path))
+                })
+                .collect();
+
+            let handle = app.handle();
+            save_user(handle, user.clone()).unwrap();
+            update_os_folders(handle.clone(), vec![folder.clone()]).unwrap();
+            update_os_videos(handle.clone(), videos.clone()).unwrap();
+
+            Self {
+                app,
+                dir,
+                user,
+                folder,
+                videos,
+            }
+        }
+
+        fn path(&self, index: usize) -> String {
+            self.videos[index].path.clone()
+        }
+
+        /// `playlist-pos`, `path`, `duration` & `file-loaded`, like mpv reports a new file
+        fn load(&self, index: usize) -> Vec<MpvEvent> {
+            vec![
+                MpvEvent::PlaylistPos(index as i64),
+                MpvEvent::Path(self.path(index)),
+                MpvEvent::Duration(1440.0),
+                MpvEvent::FileLoaded,
+            ]
+        }
+
+        /// follows the fake player through the whole session,
+        /// & returns the commands it was sent
+        fn play(
+            &self,
+            backend: FakeBackend,
+            sleep_timer: Option<ActiveSleepTimer>,
+        ) -> (Result<PlaybackSessionInfo, MpvError>, Vec<Value>) {
+            let handle = self.app.handle();
+            let request = PlayRequest {
+                playlist: self.videos.clone(),
+                start_index: 0,
+                start: None,
+                title: self.folder.title.clone(),
+                mpv_args: Vec::new(),
+            };
+            let mut player = backend.spawn(&request).unwrap();
+            let tracker = PlaybackTracker::new(
+                handle.clone(),
+                self.folder.clone(),
+                self.videos.clone(),
+                self.user.clone(),
+                None,
+            );
+            *handle.state::<PlaybackState>().0.lock().unwrap() = Some(PlaybackSession {
+                info: tracker.info(),
+                stopper: Some(player.stopper()),
+                sleep_timer,
+            });
+
+            let result = follow_session(handle, player.as_mut(), tracker);
+            let commands = backend.commands.lock().unwrap().clone();
+            (result, commands)
+        }
+
+        fn saved_videos(&self) -> Vec<OsVideo> {
+            let paths: Vec<String> = self.videos.iter().map(|vid| vid.path.clone()).collect();
+            get_os_videos_by_paths(self.app.handle(), &paths).unwrap()
+        }
+
+        fn sleep_timer(&self) -> Option<(Option<Instant>, u32)> {
+            let state = self.app.state::<PlaybackState>();
+            let session = state.0.lock().unwrap();
+            let timer = session.as_ref()?.sleep_timer.as_ref()?;
+            Some((timer.deadline, timer.episodes_left))
+        }
+    }
+
+    impl Drop for Library {
+        fn drop(&mut self) {
+            let _ = remove_dir_all(&self.dir);
+        }
+    }
+
+    fn episodes_timer(action: SleepAction, episodes_left: u32) -> Option<ActiveSleepTimer> {
+        Some(ActiveSleepTimer {
+            action,
+            deadline: None,
+            episodes_left,
+        })
+    }
+
+    #[test]
+    fn checkpoints_the_previous_file_on_file_change() {
+        let library = Library::new("playback-checkpoint");
+        // mpv quits before the `end-file` of the first episode is read
+        let mut events = library.load(0);
+        events.push(MpvEvent::TimePos(300.0));
+        events.extend(library.load(1));
+        events.push(MpvEvent::TimePos(60.0));
+        events.push(MpvEvent::Shutdown);
+
+        let (result, _) = library.play(FakeBackend::scripted(events), None);
+        let info = result.unwrap();
+        assert_eq!(info.video.map(|vid| vid.path), Some(library.path(1)));
+        assert_eq!(info.position, 60);
+
+        let videos = library.saved_videos();
+        assert_eq!(videos[0].position, 300);
+        assert!(videos[0].in_progress && !videos[0].watched);
+        assert_eq!(videos[1].position, 60);
+
+        let folder =
+            get_os_folder_by_path(library.app.handle().clone(), library.folder.path.clone())
+                .unwrap();
+        assert_eq!(
+            folder.last_watched_video.map(|vid| vid.path),
+            Some(library.path(1))
+        );
+        let user = get_user_by_id(library.app.handle().clone(), library.user.id.clone()).unwrap();
+        assert_eq!(
+            user.last_watched_video.map(|vid| vid.path),
+            Some(library.path(1))
+        );
+    }
+
+    #[test]
+    fn checkpoints_the_previous_entry_as_soon_as_the_playlist_moves_on() {
+        let library = Library::new("playback-playlist-pos");
+        let mut events = library.load(0);
+        events.push(MpvEvent::TimePos(300.0));
+        // the next entry starts before its `path` is reported
+        events.push(MpvEvent::PlaylistPos(1));
+        events.push(MpvEvent::TimePos(0.0));
+        events.extend(library.load(1).into_iter().skip(1));
+        events.push(MpvEvent::TimePos(60.0));
+        events.push(MpvEvent::PlaylistPos(-1));
+        events.push(MpvEvent::Shutdown);
+
+        let (result, _) = library.play(FakeBackend::scripted(events), None);
+        let info = result.unwrap();
+        assert_eq!(info.video.map(|vid| vid.path), Some(library.path(1)));
+
+        let videos = library.saved_videos();
+        assert_eq!(videos[0].position, 300);
+        assert_eq!(videos[1].position, 60);
+    }
+
+    #[test]
+    fn marks_videos_watched_past_the_completion_threshold() {
+        let mut library = Library::new("playback-completion");
+        let mut events = library.load(0);
+        // 90.3%
+        events.push(MpvEvent::TimePos(1300.0));
+        events.push(MpvEvent::EndFile);
+        events.extend(library.load(1));
+        // 83.3%
+        events.push(MpvEvent::TimePos(1200.0));
+        events.push(MpvEvent::Shutdown);
+
+        let (result, _) = library.play(FakeBackend::scripted(events.clone()), None);
+        result.unwrap();
+        let videos = library.saved_videos();
+        assert!(videos[0].watched && !videos[0].in_progress);
+        assert!(!videos[1].watched && videos[1].in_progress);
+
+        // 140 seconds from the end isn't within 120
+        library.user.settings.completion = CompletionPolicy::FromEnd(120);
+        save_user(library.app.handle(), library.user.clone()).unwrap();
+        update_os_videos(library.app.handle().clone(), library.videos.clone()).unwrap();
+        let (result, _) = library.play(FakeBackend::scripted(events), None);
+        result.unwrap();
+        let videos = library.saved_videos();
+        assert!(!videos[0].watched && videos[0].in_progress);
+    }
+
+    #[test]
+    fn checkpoints_keep_changes_made_during_playback() {
+        let library = Library::new("playback-concurrent-changes");
+        let handle = library.app.handle();
+        let mut tracker = PlaybackTracker::new(
+            handle.clone(),
+            library.folder.clone(),
+            library.videos.clone(),
+            library.user.clone(),
+            None,
+        );
+        for event in library.load(0) {
+            tracker.handle_event(event).unwrap();
+        }
+
+        // the window stays open, so the user keeps changing things
+        let mut user = library.user.clone();
+        user.settings.completion = CompletionPolicy::FromEnd(200);
+        save_user(handle, user).unwrap();
+        let folder = OsFolder {
+            credits_offset: Some(90),
+            ..library.folder.clone()
+        };
+        update_os_folders(handle.clone(), vec![folder]).unwrap();
+        let refreshed = OsVideo {
+            title: "Episode 1".into(),
+            ..library.saved_videos()[0].clone()
+        };
+        update_os_videos(handle.clone(), vec![refreshed]).unwrap();
+
+        tracker.handle_event(MpvEvent::TimePos(1200.0)).unwrap();
+        tracker.checkpoint().unwrap();
+
+        let user = get_user_by_id(handle.clone(), library.user.id.clone()).unwrap();
+        assert_eq!(user.settings.completion, CompletionPolicy::FromEnd(200));
+        let folder = get_os_folder_by_path(handle.clone(), library.folder.path.clone()).unwrap();
+        assert_eq!(folder.credits_offset, Some(90));
+        let video = &library.saved_videos()[0];
+        assert_eq!(video.title, "Episode 1");
+        assert_eq!(video.position, 1200);
+        // only within 200 seconds of the end once the 90 seconds of credits are cut off
+        assert!(video.watched && !video.in_progress);
+    }
+
+    #[test]
+    fn plays_the_whole_playlist_without_a_script() {
+        let library = Library::new("playback-simulated");
+
+        let (result, commands) = library.play(FakeBackend::default(), None);
+        assert_eq!(
+            result.unwrap().video.map(|vid| vid.path),
+            Some(library.path(1))
+        );
+        assert!(library
+            .saved_videos()
+            .iter()
+            .all(|vid| vid.watched && vid.position == 1440));
+        // only the first file was started with `--start`
+        assert_eq!(
+            commands
+                .iter()
+                .filter(|args| **args == json!(["set_property", "start", "none"]))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn sleep_timer_stops_after_the_last_episode() {
+        let library = Library::new("playback-sleep-episodes");
+        let mut first = library.load(0);
+        first.push(MpvEvent::TimePos(1440.0));
+        first.push(MpvEvent::EndFile);
+
+        // still counting after the first episode
+        let mut events = first.clone();
+        events.push(MpvEvent::Shutdown);
+        let (_, commands) = library.play(
+            FakeBackend::scripted(events),
+            episodes_timer(SleepAction::Quit, 2),
+        );
+        assert!(!commands.contains(&json!(["playlist-clear"])));
+        assert_eq!(library.sleep_timer(), Some((None, 1)));
+
+        // fires once the second one has loaded
+        let mut events = first;
+        events.extend(library.load(1));
+        events.push(MpvEvent::Shutdown);
+        let (_, commands) = library.play(
+            FakeBackend::scripted(events),
+            episodes_timer(SleepAction::Quit, 2),
+        );
+        assert_eq!(commands.last(), Some(&json!(["playlist-clear"])));
+        assert_eq!(library.sleep_timer(), None);
+    }
+
+    #[test]
+    fn sleep_timer_pauses_at_the_end_of_the_episode() {
+        let library = Library::new("playback-sleep-pause");
+        let mut events = library.load(0);
+        events.push(MpvEvent::Shutdown);
+
+        let (_, commands) = library.play(
+            FakeBackend::scripted(events),
+            episodes_timer(SleepAction::Pause, 1),
+        );
+        assert!(commands.ends_with(&[
+            json!(["playlist-clear"]),
+            json!(["set_property", "keep-open", "yes"]),
+        ]));
+    }
+
+    #[test]
+    fn sleep_timer_pauses_once_the_minutes_ran_out() {
+        let library = Library::new("playback-sleep-minutes");
+        let mut events = library.load(0);
+        events.push(MpvEvent::TimePos(600.0));
+        events.push(MpvEvent::Shutdown);
+
+        let timer = ActiveSleepTimer {
+            action: SleepAction::Pause,
+            deadline: Some(Instant::now()),
+            episodes_left: 0,
+        };
+        let (_, commands) = library.play(FakeBackend::scripted(events), Some(timer));
+        assert_eq!(
+            commands.first(),
+            Some(&json!(["set_property", "pause", true]))
+        );
+        // the position is saved as soon as it's paused
+        assert_eq!(library.sleep_timer(), None);
+        assert!(library.saved_videos()[0].in_progress);
+    }
+
+    #[test]
+    fn only_one_session_holds_the_slot_until_it_is_released() {
+        let state = PlaybackState::default();
+        let info = || PlaybackSessionInfo {
+            main_folder_path: "/shows/show".into(),
+            video: Some(OsVideo::stub("/shows/show", "/shows/show/01.mkv")),
+            position: 0,
+            duration: 1440,
+            shuffle: None,
+            chapter: None,
+        };
+
+        state.reserve(info()).unwrap();
+        assert!(matches!(
+            state.reserve(info()),
+            Err(MpvError::PlaybackSessionRunning(path)) if path == "/shows/show/01.mkv"
+        ));
+        // the player hasn't spawned yet
+        assert!(state.0.lock().unwrap().as_ref().unwrap().stopper.is_none());
+
+        state.release();
+        assert!(state.reserve(info()).is_ok());
+    }
+
+    #[test]
+    fn classifies_how_the_session_ended() {
+        let library = Library::new("playback-exit");
+        let mut events = library.load(0);
+        events.push(MpvEvent::TimePos(300.0));
+        events.push(MpvEvent::Shutdown);
+        let ipc_error = || MpvError::Ipc(MpvIpcError::ConnectTimeout("/tmp/mpv.sock".into()));
+
+        let (followed, _) = library.play(FakeBackend::scripted(events.clone()), None);
+        assert!(session_result(followed, Ok(())).is_ok_and(|info| info.position == 300));
+
+        // quit-watch-later saved the position like any other quit
+        let (followed, _) = library.play(FakeBackend::scripted(events.clone()), None);
+        assert!(session_result(followed, Err(MpvError::QuitWatchLater)).is_ok());
+
+        let (followed, _) = library.play(FakeBackend::scripted(events), None);
+        assert!(matches!(
+            session_result(followed, Err(MpvError::ExitCode(1, String::new()))),
+            Err(MpvError::ExitCode(1, _))
+        ));
+
+        // mpv's exit explains why it never connected
+        assert!(matches!(
+            session_result(
+                Err(ipc_error()),
+                Err(MpvError::FileNotFound("01.mkv".into()))
+            ),
+            Err(MpvError::FileNotFound(_))
+        ));
+        // unless it was only killed because it never connected
+        assert!(matches!(
+            session_result(Err(ipc_error()), Err(MpvError::KilledBySignal(9))),
+            Err(MpvError::Ipc(_))
+        ));
+        assert!(matches!(
+            session_result(Err(ipc_error()), Ok(())),
+            Err(MpvError::Ipc(_))
+        ));
+    }
+}
@@ -0,0 +1,613 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use std::{env, process};
+
+use serde_json::{json, Value};
+
+use crate::database::data::v2::{OsVideo, PlayerBackendKind};
+use crate::error::{MpvError, MpvSettingsError};
+use crate::ipc::{ipc_socket_path, MpvEvent, MpvIpcClient};
+use crate::mpv::{classify_mpv_exit, write_playlist};
+
+///! The players mpvshelf can play through.
+///!
+///! `mpv` is followed through its JSON IPC.
+///! Any other player can be run from a command template,
+///! & reports its progress by appending to a JSON-lines file,
+///! one mpv IPC event per line:
+///! ```text
+///! {"event":"property-change","name":"path","data":"/videos/01.mkv"}
+///! {"event":"property-change","name":"duration","data":1420.0}
+///! {"event":"file-loaded"}
+///! {"event":"property-change","name":"time-pos","data":12.5}
+///! {"event":"end-file"}
+///! ```
+///! https://mpv.io/manual/master/#json-ipc
+
+/// sessions started by this process
+static SESSIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// `<pid>-<n>`, so a session never reuses the socket,
+/// progress file or playlist of one that's still shutting down.
+fn next_session_id() -> String {
+    format!(
+        "{}-{}",
+        process::id(),
+        SESSIONS.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Everything a player needs to start playing.
+pub struct PlayRequest {
+    /// a single video when autoplay is off
+    pub playlist: Vec<OsVideo>,
+    /// index into `playlist`
+    pub start_index: usize,
+    /// * in seconds.
+    pub start: Option<u64>,
+    pub title: String,
+    /// only passed to mpv & its forks
+    pub mpv_args: Vec<String>,
+}
+
+impl PlayRequest {
+    pub fn video(&self) -> Option<&OsVideo> {
+        self.playlist.get(self.start_index)
+    }
+}
+
+pub trait PlayerBackend {
+    /// starts playing & returns as soon as the player is running
+    fn spawn(&self, request: &PlayRequest) -> Result<Box<dyn PlayerSession>, MpvError>;
+}
+
+/// A running player, followed on the playback thread.
+pub trait PlayerSession: Send {
+    /// Blocks until the next event.
+    /// Returns `None` once the player has exited.
+    fn next_event(&mut self) -> Result<Option<MpvEvent>, MpvError>;
+
+    /// sends an mpv IPC command, eg: `json!(["set_property", "pause", true])`.
+    /// players that can't be controlled ignore everything but `quit`.
+    fn command(&mut self, args: Value) -> Result<(), MpvError>;
+
+    /// a handle to stop the player from any thread
+    fn stopper(&self) -> Box<dyn PlayerStopper>;
+
+    /// waits for the player to exit, killing it first with `kill`,
+    /// & returns how it exited
+    fn wait(&mut self, kill: bool) -> Result<(), MpvError>;
+}
+
+pub trait PlayerStopper: Send {
+    fn stop(&mut self) -> Result<(), MpvError>;
+}
+
+pub fn player_backend(kind: &PlayerBackendKind, exe_path: Option<&str>) -> Box<dyn PlayerBackend> {
+    match kind {
+        PlayerBackendKind::Mpv => Box::new(MpvBackend {
+            exe_path: exe_path.map(String::from),
+        }),
+        PlayerBackendKind::CommandTemplate(template) => Box::new(CommandTemplateBackend {
+            template: template.clone(),
+        }),
+    }
+}
+
+/// A spawned player process, shared with its stopper.
+struct PlayerProcess {
+    child: StoppableChild,
+    stderr: Option<JoinHandle<String>>,
+}
+
+/// killing the player to stop it is how its session was meant to end,
+/// so the exit that follows isn't an error.
+#[derive(Clone)]
+struct StoppableChild {
+    child: Arc<Mutex<Child>>,
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl StoppableChild {
+    fn stop(&self) -> Result<(), MpvError> {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        self.child.lock().unwrap().kill()?;
+        Ok(())
+    }
+}
+
+impl PlayerProcess {
+    fn spawn(command: &mut Command) -> Result<Self, MpvError> {
+        let program = command.get_program().to_string_lossy().to_string();
+        let mut child = command
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => MpvError::AbsolutePathNotFound(program),
+                _ => MpvError::IoError(e),
+            })?;
+
+        // the player blocks once the stderr pipe fills up, so keep draining it
+        let stderr = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut buf = String::new();
+                let _ = stderr.read_to_string(&mut buf);
+                buf
+            })
+        });
+
+        Ok(Self {
+            child: StoppableChild {
+                child: Arc::new(Mutex::new(child)),
+                stop_requested: Arc::new(AtomicBool::new(false)),
+            },
+            stderr,
+        })
+    }
+
+    fn has_exited(&self) -> Result<bool, MpvError> {
+        Ok(self.child.child.lock().unwrap().try_wait()?.is_some())
+    }
+
+    fn kill(&self) -> Result<(), MpvError> {
+        self.child.child.lock().unwrap().kill()?;
+        Ok(())
+    }
+
+    fn wait(&mut self, kill: bool) -> Result<(), MpvError> {
+        if kill {
+            // it may have exited on its own already
+            let _ = self.kill();
+        }
+        // poll, so the stopper can still get to the child
+        let status = loop {
+            if let Some(status) = self.child.child.lock().unwrap().try_wait()? {
+                break status;
+            }
+            thread::sleep(Duration::from_millis(50));
+        };
+        let stderr = self
+            .stderr
+            .take()
+            .and_then(|stderr| stderr.join().ok())
+            .unwrap_or_default();
+
+        match classify_mpv_exit(status, &stderr) {
+            // SIGKILL on unix, exit code 1 on windows
+            Err(MpvError::KilledBySignal(_) | MpvError::ExitCode(..))
+                if self.child.stop_requested.load(Ordering::SeqCst) =>
+            {
+                Ok(())
+            }
+            exit => exit,
+        }
+    }
+}
+
+struct KillStopper(StoppableChild);
+
+impl PlayerStopper for KillStopper {
+    fn stop(&mut self) -> Result<(), MpvError> {
+        self.0.stop()
+    }
+}
+
+pub struct MpvBackend {
+    pub exe_path: Option<String>,
+}
+
+impl PlayerBackend for MpvBackend {
+    fn spawn(&self, request: &PlayRequest) -> Result<Box<dyn PlayerSession>, MpvError> {
+        let video = request
+            .video()
+            .ok_or_else(|| MpvError::OsVideoNotFound(request.title.clone()))?;
+
+        let session_id = next_session_id();
+        let playlist_path = if request.playlist.len() > 1 {
+            Some(write_playlist(&session_id, &request.playlist)?)
+        } else {
+            None
+        };
+        let mut args = match &playlist_path {
+            Some(playlist_path) => vec![
+                format!("--playlist-start={}", request.start_index),
+                format!("--playlist={}", playlist_path.to_string_lossy()),
+            ],
+            None => vec![video.path.clone()],
+        };
+        args.push(format!("--title={} | mpvshelf", request.title));
+        if let Some(start) = request.start {
+            args.push(format!("--start={start}"));
+        }
+        args.extend(request.mpv_args.iter().cloned());
+
+        let socket_path = ipc_socket_path(&session_id);
+        args.push(format!("--input-ipc-server={socket_path}"));
+
+        let mpv_exe = self.exe_path.as_deref().unwrap_or("mpv");
+        let process = PlayerProcess::spawn(Command::new(mpv_exe).args(&args))?;
+
+        Ok(Box::new(MpvSession {
+            process,
+            socket_path,
+            playlist_path,
+            client: None,
+        }))
+    }
+}
+
+struct MpvSession {
+    process: PlayerProcess,
+    socket_path: String,
+    /// only written for more than one video
+    playlist_path: Option<PathBuf>,
+    client: Option<MpvIpcClient>,
+}
+
+impl MpvSession {
+    /// mpv opens its ipc server shortly after starting,
+    /// returns `None` if it exits before then.
+    fn connect(&mut self) -> Result<Option<&mut MpvIpcClient>, MpvError> {
+        if self.client.is_none() {
+            let mut attempts = 0;
+            let mut client = loop {
+                match MpvIpcClient::connect(&self.socket_path, Duration::from_millis(500)) {
+                    Ok(client) => break client,
+                    Err(_) if self.process.has_exited()? => return Ok(None),
+                    Err(_) if attempts < 20 => attempts += 1,
+                    Err(e) => return Err(e.into()),
+                }
+            };
+            client.observe_properties()?;
+            self.client = Some(client);
+        }
+        Ok(self.client.as_mut())
+    }
+}
+
+impl PlayerSession for MpvSession {
+    fn next_event(&mut self) -> Result<Option<MpvEvent>, MpvError> {
+        match self.connect()? {
+            Some(client) => Ok(client.next_event()?),
+            None => Ok(None),
+        }
+    }
+
+    fn command(&mut self, args: Value) -> Result<(), MpvError> {
+        if let Some(client) = self.client.as_mut() {
+            client.command(args)?;
+        }
+        Ok(())
+    }
+
+    fn stopper(&self) -> Box<dyn PlayerStopper> {
+        Box::new(MpvStopper {
+            socket_path: self.socket_path.clone(),
+            child: self.process.child.clone(),
+        })
+    }
+
+    fn wait(&mut self, kill: bool) -> Result<(), MpvError> {
+        let result = self.process.wait(kill);
+        // mpv leaves its socket behind when it's killed
+        #[cfg(not(windows))]
+        let _ = fs::remove_file(&self.socket_path);
+        if let Some(playlist_path) = &self.playlist_path {
+            let _ = fs::remove_file(playlist_path);
+        }
+        result
+    }
+}
+
+struct MpvStopper {
+    socket_path: String,
+    child: StoppableChild,
+}
+
+impl PlayerStopper for MpvStopper {
+    /// quitting through ipc lets the session save the last position,
+    /// only kill mpv if it doesn't respond
+    fn stop(&mut self) -> Result<(), MpvError> {
+        match MpvIpcClient::connect(&self.socket_path, Duration::from_secs(1)) {
+            Ok(mut client) => client.command(json!(["quit"]))?,
+            Err(_) => self.child.stop()?,
+        }
+        Ok(())
+    }
+}
+
+/// Runs any player from a template, eg:
+/// `["my-player", "--start={start}", "--progress={progress_file}", "{playlist}"]`.
+///
+/// Placeholders: `{path}`, `{playlist}` (an m3u file), `{playlist_start}`,
+/// `{start}`, `{title}` & `{progress_file}`.
+/// Arguments with a placeholder that has no value, eg: `{start}`, are left out.
+pub struct CommandTemplateBackend {
+    pub template: Vec<String>,
+}
+
+impl CommandTemplateBackend {
+    fn fill(arg: &str, values: &[(&str, Option<String>)]) -> Option<String> {
+        let mut arg = arg.to_string();
+        for (placeholder, value) in values {
+            if arg.contains(placeholder) {
+                arg = arg.replace(placeholder, value.as_deref()?);
+            }
+        }
+        Some(arg)
+    }
+}
+
+impl PlayerBackend for CommandTemplateBackend {
+    fn spawn(&self, request: &PlayRequest) -> Result<Box<dyn PlayerSession>, MpvError> {
+        let video = request
+            .video()
+            .ok_or_else(|| MpvError::OsVideoNotFound(request.title.clone()))?;
+        let Some((program, template_args)) = self.template.split_first() else {
+            return Err(MpvSettingsError::EmptyCommandTemplate.into());
+        };
+
+        let session_id = next_session_id();
+        let progress_file = progress_file_path(&session_id);
+        File::create(&progress_file)?;
+        let playlist_path = write_playlist(&session_id, &request.playlist)?;
+
+        let values = [
+            ("{path}", Some(video.path.clone())),
+            (
+                "{playlist}",
+                Some(playlist_path.to_string_lossy().to_string()),
+            ),
+            ("{playlist_start}", Some(request.start_index.to_string())),
+            ("{start}", request.start.map(|start| start.to_string())),
+            ("{title}", Some(request.title.clone())),
+            (
+                "{progress_file}",
+                Some(progress_file.to_string_lossy().to_string()),
+            ),
+        ];
+        let args: Vec<String> = template_args
+            .iter()
+            .filter_map(|arg| Self::fill(arg, &values))
+            .collect();
+
+        let process = PlayerProcess::spawn(Command::new(program).args(&args))?;
+
+        Ok(Box::new(CommandTemplateSession {
+            process,
+            progress: BufReader::new(File::open(&progress_file)?),
+            progress_file,
+            playlist_path,
+            line: String::new(),
+            exited: false,
+        }))
+    }
+}
+
+fn progress_file_path(session_id: &str) -> PathBuf {
+    env::temp_dir().join(format!("mpvshelf-{session_id}-progress.jsonl"))
+}
+
+struct CommandTemplateSession {
+    process: PlayerProcess,
+    progress: BufReader<File>,
+    progress_file: PathBuf,
+    playlist_path: PathBuf,
+    /// the player may still be writing the last line
+    line: String,
+    exited: bool,
+}
+
+impl PlayerSession for CommandTemplateSession {
+    fn next_event(&mut self) -> Result<Option<MpvEvent>, MpvError> {
+        loop {
+            let read = self.progress.read_line(&mut self.line)?;
+            if self.line.ends_with('\n') || (read == 0 && self.exited && !self.line.is_empty()) {
+                let line = std::mem::take(&mut self.line);
+                match MpvEvent::from_line(line.trim()) {
+                    Ok(Some(event)) => return Ok(Some(event)),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("skipping invalid progress line {line:?}: {e}"),
+                }
+                continue;
+            }
+            if read > 0 {
+                continue;
+            }
+
+            // read whatever was written right before it exited, then stop
+            if self.exited {
+                return Ok(None);
+            }
+            self.exited = self.process.has_exited()?;
+            if !self.exited {
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+
+    fn command(&mut self, args: Value) -> Result<(), MpvError> {
+        if args == json!(["quit"]) {
+            self.process.child.stop()?;
+        }
+        Ok(())
+    }
+
+    fn stopper(&self) -> Box<dyn PlayerStopper> {
+        Box::new(KillStopper(self.process.child.clone()))
+    }
+
+    fn wait(&mut self, kill: bool) -> Result<(), MpvError> {
+        let result = self.process.wait(kill);
+        let _ = fs::remove_file(&self.progress_file);
+        let _ = fs::remove_file(&self.playlist_path);
+        result
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::{json, Value};
+
+    use super::{PlayRequest, PlayerBackend, PlayerSession, PlayerStopper};
+    use crate::error::MpvError;
+    use crate::ipc::MpvEvent;
+
+    /// Plays without a player, for testing playback flows.
+    ///
+    /// By default it plays through the playlist from the requested video & start,
+    /// reporting one `time-pos` per minute of each video.
+    #[derive(Default)]
+    pub struct FakeBackend {
+        /// replaces the simulated playback
+        pub events: Option<Vec<MpvEvent>>,
+        /// every command sent to the player, in order
+        pub commands: Arc<Mutex<Vec<Value>>>,
+    }
+
+    impl FakeBackend {
+        pub fn scripted(events: Vec<MpvEvent>) -> Self {
+            Self {
+                events: Some(events),
+                ..Default::default()
+            }
+        }
+
+        fn simulate(request: &PlayRequest) -> Vec<MpvEvent> {
+            let mut events = Vec::new();
+            for (i, vid) in request
+                .playlist
+                .iter()
+                .enumerate()
+                .skip(request.start_index)
+            {
+                let duration = if vid.duration > 0 { vid.duration } else { 60 };
+                let start = if i == request.start_index {
+                    request.start.unwrap_or(0)
+                } else {
+                    0
+                };
+
+                events.push(MpvEvent::PlaylistPos(i as i64));
+                events.push(MpvEvent::Path(vid.path.clone()));
+                events.push(MpvEvent::Duration(duration as f64));
+                events.push(MpvEvent::FileLoaded);
+                events.extend(
+                    (start..=duration)
+                        .step_by(60)
+                        .chain([duration])
+                        .map(|pos| MpvEvent::TimePos(pos as f64)),
+                );
+                events.push(MpvEvent::EndFile);
+            }
+            events.push(MpvEvent::Shutdown);
+            events
+        }
+    }
+
+    impl PlayerBackend for FakeBackend {
+        fn spawn(&self, request: &PlayRequest) -> Result<Box<dyn PlayerSession>, MpvError> {
+            let events = self
+                .events
+                .clone()
+                .unwrap_or_else(|| Self::simulate(request));
+
+            Ok(Box::new(FakeSession {
+                events: events.into(),
+                commands: self.commands.clone(),
+                stopped: Arc::new(AtomicBool::new(false)),
+            }))
+        }
+    }
+
+    struct FakeSession {
+        events: VecDeque<MpvEvent>,
+        commands: Arc<Mutex<Vec<Value>>>,
+        stopped: Arc<AtomicBool>,
+    }
+
+    impl PlayerSession for FakeSession {
+        fn next_event(&mut self) -> Result<Option<MpvEvent>, MpvError> {
+            if self.stopped.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+            Ok(self.events.pop_front())
+        }
+
+        fn command(&mut self, args: Value) -> Result<(), MpvError> {
+            if args == json!(["quit"]) {
+                self.stopped.store(true, Ordering::SeqCst);
+            }
+            self.commands.lock().unwrap().push(args);
+            Ok(())
+        }
+
+        fn stopper(&self) -> Box<dyn PlayerStopper> {
+            Box::new(FakeStopper(self.stopped.clone()))
+        }
+
+        fn wait(&mut self, _kill: bool) -> Result<(), MpvError> {
+            Ok(())
+        }
+    }
+
+    struct FakeStopper(Arc<AtomicBool>);
+
+    impl PlayerStopper for FakeStopper {
+        fn stop(&mut self) -> Result<(), MpvError> {
+            self.0.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn sleeping_player() -> Box<dyn PlayerSession> {
+        let backend = CommandTemplateBackend {
+            template: vec!["sleep".into(), "30".into()],
+        };
+        let request = PlayRequest {
+            playlist: vec![OsVideo::stub("/shows", "/shows/01.mkv")],
+            start_index: 0,
+            start: None,
+            title: "show".into(),
+            mpv_args: Vec::new(),
+        };
+        backend.spawn(&request).unwrap()
+    }
+
+    #[test]
+    fn stopping_a_player_ends_its_session_normally() {
+        let mut player = sleeping_player();
+        player.stopper().stop().unwrap();
+        assert!(player.wait(false).is_ok());
+
+        // the quit a sleep timer sends is a stop too
+        let mut player = sleeping_player();
+        player.command(json!(["quit"])).unwrap();
+        assert!(player.wait(false).is_ok());
+    }
+
+    #[test]
+    fn a_player_killed_for_anything_else_is_an_error() {
+        let mut player = sleeping_player();
+        assert!(matches!(
+            player.wait(true),
+            Err(MpvError::KilledBySignal(9))
+        ));
+    }
+}
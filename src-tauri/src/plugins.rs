@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tauri::{command, AppHandle, Manager};
+
+use crate::database::data::v2::{MpvPlugin, MpvPluginKind, MpvSettings, User};
+use crate::database::{delete_mpv_plugin, get_mpv_plugin, get_mpv_plugins, update_mpv_plugins};
+use crate::error::PluginError;
+use crate::misc::get_date_time;
+
+///! Manages the mpv scripts & script-opts installed into the plugins dir,
+///! `portable_config/plugins` unless the user picked another one.
+
+/// script-opts are kept apart from the scripts,
+/// so they're never mistaken for a script directory.
+const SCRIPT_OPTS_DIR: &str = "script-opts";
+
+fn plugins_dir(handle: &AppHandle, mpv_settings: &MpvSettings) -> Result<PathBuf, PluginError> {
+    let app_data_dir = handle.path().app_data_dir()?;
+    Ok(mpv_settings.plugins_dir(&app_data_dir))
+}
+
+fn plugin_kind(path: &Path) -> Option<MpvPluginKind> {
+    if path.is_dir() {
+        let has_main = ["main.lua", "main.js"]
+            .iter()
+            .any(|main| path.join(main).is_file());
+        return has_main.then_some(MpvPluginKind::Script);
+    }
+
+    match path.extension()?.to_str()? {
+        "lua" | "js" => Some(MpvPluginKind::Script),
+        "conf" => Some(MpvPluginKind::ScriptOpts),
+        _ => None,
+    }
+}
+
+fn copy_dir_all(src: &Path, dst: &Path) -> Result<(), std::io::Error> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let entry_path = entry.path();
+        if entry_path.is_dir() {
+            copy_dir_all(&entry_path, &dst.join(entry.file_name()))?;
+        } else {
+            fs::copy(&entry_path, dst.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+/// copies a script, script dir or script-opts `.conf` into the plugins dir.
+/// new plugins are enabled right away.
+#[command]
+pub fn install_mpv_plugin(
+    handle: AppHandle,
+    user: User,
+    source_path: String,
+) -> Result<MpvPlugin, PluginError> {
+    let source = Path::new(&source_path);
+    let kind = plugin_kind(source).ok_or_else(|| PluginError::Unsupported(source_path.clone()))?;
+    let name = source
+        .file_name()
+        .ok_or_else(|| PluginError::Unsupported(source_path.clone()))?
+        .to_string_lossy()
+        .to_string();
+
+    if get_mpv_plugin(&handle, &name)?.is_some() {
+        return Err(PluginError::AlreadyInstalled(name));
+    }
+
+    let mut installed_dir = plugins_dir(&handle, &user.settings.mpv_settings)?;
+    if kind == MpvPluginKind::ScriptOpts {
+        installed_dir = installed_dir.join(SCRIPT_OPTS_DIR);
+    }
+    fs::create_dir_all(&installed_dir)?;
+
+    let installed_path = installed_dir.join(&name);
+    if source.is_dir() {
+        copy_dir_all(source, &installed_path)?;
+    } else {
+        fs::copy(source, &installed_path)?;
+    }
+
+    let (install_date, install_time) = get_date_time();
+    let plugin = MpvPlugin {
+        name,
+        kind,
+        source_path,
+        installed_path: installed_path.to_string_lossy().to_string(),
+        enabled: true,
+        install_date,
+        install_time,
+    };
+    update_mpv_plugins(&handle, vec![plugin.clone()])?;
+
+    Ok(plugin)
+}
+
+fn set_mpv_plugin_enabled(
+    handle: &AppHandle,
+    name: String,
+    enabled: bool,
+) -> Result<MpvPlugin, PluginError> {
+    let mut plugin = get_mpv_plugin(handle, &name)?.ok_or(PluginError::NotFound(name))?;
+    plugin.enabled = enabled;
+    update_mpv_plugins(handle, vec![plugin.clone()])?;
+    Ok(plugin)
+}
+
+#[command]
+pub fn enable_mpv_plugin(handle: AppHandle, name: String) -> Result<MpvPlugin, PluginError> {
+    set_mpv_plugin_enabled(&handle, name, true)
+}
+
+#[command]
+pub fn disable_mpv_plugin(handle: AppHandle, name: String) -> Result<MpvPlugin, PluginError> {
+    set_mpv_plugin_enabled(&handle, name, false)
+}
+
+#[command]
+pub fn remove_mpv_plugin(handle: AppHandle, name: String) -> Result<(), PluginError> {
+    let plugin = get_mpv_plugin(&handle, &name)?.ok_or(PluginError::NotFound(name))?;
+
+    let installed_path = Path::new(&plugin.installed_path);
+    let removed = if installed_path.is_dir() {
+        fs::remove_dir_all(installed_path)
+    } else {
+        fs::remove_file(installed_path)
+    };
+    if let Err(e) = removed {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            return Err(PluginError::Io(e));
+        }
+    }
+
+    delete_mpv_plugin(&handle, plugin)?;
+    Ok(())
+}
+
+/// turns every `key=value` line of a script-opts file into a `--script-opts-append` arg
+fn script_opts_args(plugin: &MpvPlugin) -> Result<Vec<String>, PluginError> {
+    let path = Path::new(&plugin.installed_path);
+    let Some(script_name) = path.file_stem() else {
+        return Ok(Vec::new());
+    };
+    let script_name = script_name.to_string_lossy();
+
+    let args = fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            format!(
+                "--script-opts-append={script_name}-{}={}",
+                key.trim(),
+                value.trim()
+            )
+        })
+        .collect();
+
+    Ok(args)
+}
+
+/// mpv args for every enabled plugin,
+/// plus any script dropped into `plugins_dir` without being installed.
+pub fn plugin_args(handle: &AppHandle, plugins_dir: &Path) -> Result<Vec<String>, PluginError> {
+    let plugins = get_mpv_plugins(handle.clone())?;
+    let managed_paths: HashSet<PathBuf> = plugins
+        .iter()
+        .map(|plugin| PathBuf::from(&plugin.installed_path))
+        .collect();
+
+    let mut args = Vec::new();
+    for plugin in plugins.iter().filter(|plugin| plugin.enabled) {
+        match plugin.kind {
+            MpvPluginKind::Script => args.push(format!("--script={}", plugin.installed_path)),
+            MpvPluginKind::ScriptOpts => args.extend(script_opts_args(plugin)?),
+        }
+    }
+
+    if plugins_dir.exists() {
+        let mut unmanaged: Vec<PathBuf> = fs::read_dir(plugins_dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| !managed_paths.contains(path))
+            .filter(|path| path.file_name().is_some_and(|name| name != SCRIPT_OPTS_DIR))
+            .filter(|path| plugin_kind(path) == Some(MpvPluginKind::Script))
+            .collect();
+        unmanaged.sort();
+        args.extend(
+            unmanaged
+                .into_iter()
+                .map(|script| format!("--script={}", script.to_string_lossy())),
+        );
+    }
+
+    Ok(args)
+}
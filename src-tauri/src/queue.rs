@@ -0,0 +1,114 @@
+use tauri::{command, AppHandle, Manager};
+
+use crate::database::data::v2::{OsVideo, User};
+use crate::database::{get_os_folder_by_path, get_os_videos_by_paths, get_queue, save_queue};
+use crate::error::{MpvError, QueueError};
+use crate::mpv::{launch_mpv, start_position, LaunchOptions};
+
+///! The persisted "Up Next" queue, played as a single mpv playlist across folders.
+
+fn queued_videos(handle: &AppHandle, user_id: &str) -> Result<Vec<OsVideo>, QueueError> {
+    let queue = get_queue(handle, user_id)?;
+    Ok(get_os_videos_by_paths(handle, &queue.video_paths)?)
+}
+
+#[command]
+pub fn get_queued_videos(handle: AppHandle, user_id: String) -> Result<Vec<OsVideo>, QueueError> {
+    queued_videos(&handle, &user_id)
+}
+
+/// appends the videos, or puts them first with `play_next`.
+/// videos already in the queue are skipped.
+#[command]
+pub fn enqueue_videos(
+    handle: AppHandle,
+    user_id: String,
+    video_paths: Vec<String>,
+    play_next: Option<bool>,
+) -> Result<Vec<OsVideo>, QueueError> {
+    let mut queue = get_queue(&handle, &user_id)?;
+    let mut new_paths: Vec<String> = Vec::with_capacity(video_paths.len());
+    for path in video_paths {
+        if !queue.video_paths.contains(&path) && !new_paths.contains(&path) {
+            new_paths.push(path);
+        }
+    }
+
+    if play_next.unwrap_or(false) {
+        queue.video_paths.splice(0..0, new_paths);
+    } else {
+        queue.video_paths.extend(new_paths);
+    }
+    save_queue(&handle, queue)?;
+
+    queued_videos(&handle, &user_id)
+}
+
+#[command]
+pub fn move_queued_video(
+    handle: AppHandle,
+    user_id: String,
+    from: usize,
+    to: usize,
+) -> Result<Vec<OsVideo>, QueueError> {
+    let mut queue = get_queue(&handle, &user_id)?;
+    let len = queue.video_paths.len();
+    if from >= len || to >= len {
+        return Err(QueueError::IndexOutOfBounds(from.max(to), len));
+    }
+
+    let path = queue.video_paths.remove(from);
+    queue.video_paths.insert(to, path);
+    save_queue(&handle, queue)?;
+
+    queued_videos(&handle, &user_id)
+}
+
+#[command]
+pub fn remove_queued_video(
+    handle: AppHandle,
+    user_id: String,
+    video_path: String,
+) -> Result<Vec<OsVideo>, QueueError> {
+    let mut queue = get_queue(&handle, &user_id)?;
+    queue.video_paths.retain(|path| *path != video_path);
+    save_queue(&handle, queue)?;
+
+    queued_videos(&handle, &user_id)
+}
+
+#[command]
+pub fn clear_queue(handle: AppHandle, user_id: String) -> Result<(), QueueError> {
+    let mut queue = get_queue(&handle, &user_id)?;
+    queue.video_paths.clear();
+    save_queue(&handle, queue)?;
+
+    Ok(())
+}
+
+/// plays the whole queue as one playlist, starting at `video_path` or the first video.
+/// progress is saved to each video's own folder.
+#[command]
+pub async fn play_queue(
+    handle: AppHandle,
+    user: User,
+    video_path: Option<String>,
+) -> Result<(), QueueError> {
+    let os_videos = queued_videos(&handle, &user.id)?;
+    let video = match &video_path {
+        Some(path) => os_videos.iter().find(|vid| vid.path == *path),
+        None => os_videos.first(),
+    }
+    .cloned()
+    .ok_or(QueueError::Empty)?;
+
+    let main_folder = get_os_folder_by_path(handle.clone(), video.main_folder_path.clone())?;
+    let options = LaunchOptions {
+        start: start_position(&handle, &main_folder, &video, &user.settings.mpv_settings),
+        autoplay: Some(true),
+        ..Default::default()
+    };
+    launch_mpv(handle, main_folder, os_videos, video, user, options)?;
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use hashbrown::HashMap;
+use serde::Serialize;
+use tauri::{command, AppHandle, Emitter, Manager};
+
+use crate::error::ReadDirError;
+
+///! Progress & cancellation of library scans.
+///! Every scan emits `scan-progress` after each folder it reads,
+///! each video it fingerprints & each thumbnail it makes.
+
+/// the running scans, by id
+#[derive(Default)]
+pub struct ScanState(Mutex<HashMap<String, Arc<AtomicBool>>>);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ScanProgress {
+    pub scan_id: String,
+    pub dirs_visited: usize,
+    pub videos_found: usize,
+    /// videos scanned before fingerprints existed
+    pub fingerprints_done: usize,
+    pub fingerprints_total: usize,
+    pub thumbnails_done: usize,
+    /// only known once every folder has been read
+    pub thumbnails_total: usize,
+}
+
+/// a running scan, shared by the rayon threads reading its folders
+pub struct Scan {
+    handle: AppHandle,
+    id: String,
+    cancelled: Arc<AtomicBool>,
+    dirs_visited: AtomicUsize,
+    videos_found: AtomicUsize,
+    fingerprints_done: AtomicUsize,
+    fingerprints_total: AtomicUsize,
+    thumbnails_done: AtomicUsize,
+    thumbnails_total: AtomicUsize,
+}
+
+impl Scan {
+    /// the scan can be cancelled by its id until it's dropped
+    pub fn start(handle: &AppHandle, id: String) -> Self {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        handle
+            .state::<ScanState>()
+            .0
+            .lock()
+            .unwrap()
+            .insert(id.clone(), cancelled.clone());
+
+        Self {
+            handle: handle.clone(),
+            id,
+            cancelled,
+            dirs_visited: AtomicUsize::new(0),
+            videos_found: AtomicUsize::new(0),
+            fingerprints_done: AtomicUsize::new(0),
+            fingerprints_total: AtomicUsize::new(0),
+            thumbnails_done: AtomicUsize::new(0),
+            thumbnails_total: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn check_cancelled(&self) -> Result<(), ReadDirError> {
+        if self.is_cancelled() {
+            return Err(ReadDirError::Cancelled(self.id.clone()));
+        }
+        Ok(())
+    }
+
+    pub fn dir_visited(&self, videos_found: usize) {
+        self.dirs_visited.fetch_add(1, Ordering::Relaxed);
+        self.videos_found.fetch_add(videos_found, Ordering::Relaxed);
+        self.emit();
+    }
+
+    pub fn fingerprints_started(&self, total: usize) {
+        self.fingerprints_total.store(total, Ordering::Relaxed);
+        self.emit();
+    }
+
+    pub fn fingerprint_done(&self) {
+        self.fingerprints_done.fetch_add(1, Ordering::Relaxed);
+        self.emit();
+    }
+
+    pub fn thumbnails_started(&self, total: usize) {
+        self.thumbnails_total.store(total, Ordering::Relaxed);
+        self.emit();
+    }
+
+    pub fn thumbnail_done(&self) {
+        self.thumbnails_done.fetch_add(1, Ordering::Relaxed);
+        self.emit();
+    }
+
+    fn emit(&self) {
+        let progress = ScanProgress {
+            scan_id: self.id.clone(),
+            dirs_visited: self.dirs_visited.load(Ordering::Relaxed),
+            videos_found: self.videos_found.load(Ordering::Relaxed),
+            fingerprints_done: self.fingerprints_done.load(Ordering::Relaxed),
+            fingerprints_total: self.fingerprints_total.load(Ordering::Relaxed),
+            thumbnails_done: self.thumbnails_done.load(Ordering::Relaxed),
+            thumbnails_total: self.thumbnails_total.load(Ordering::Relaxed),
+        };
+        if let Err(e) = self.handle.emit("scan-progress", progress) {
+            eprintln!("failed to emit scan-progress: {e}");
+        }
+    }
+}
+
+impl Drop for Scan {
+    fn drop(&mut self) {
+        let state = self.handle.state::<ScanState>();
+        let mut scans = state.0.lock().unwrap();
+        // a newer scan may have taken over the id
+        if scans
+            .get(&self.id)
+            .is_some_and(|cancelled| Arc::ptr_eq(cancelled, &self.cancelled))
+        {
+            scans.remove(&self.id);
+        }
+    }
+}
+
+/// stops the scan before it writes anything to the database.
+/// returns `false` if no scan with that id is running.
+#[command]
+pub fn cancel_scan(handle: AppHandle, scan_id: String) -> bool {
+    let state = handle.state::<ScanState>();
+    let scans = state.0.lock().unwrap();
+    match scans.get(&scan_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use std::{env, fs, io};
+
+use tauri::{command, AppHandle, Manager};
+
+use crate::database::data::v2::{
+    CompletionPolicy, OsVideo, Track, TrackSelection, User, WatchLaterPolicy,
+};
+use crate::database::{get_os_folder_by_path, get_os_videos_by_user, update_os_videos};
+use crate::error::WatchLaterError;
+
+///! Imports resume positions from mpv's `watch_later` directory.
+///! https://mpv.io/manual/master/#resuming-playback
+
+/// A parsed `watch_later` file.
+#[derive(Debug, Clone)]
+pub struct WatchLaterEntry {
+    /// * in seconds.
+    pub start: Option<f64>,
+    /// only the track ids, mpv doesn't save the languages
+    pub tracks: TrackSelection,
+    pub speed: Option<f64>,
+    pub modified: Option<SystemTime>,
+}
+
+/// mpv names every watch_later file after the uppercase md5 hash of the played path.
+pub fn watch_later_hash(path: &str) -> String {
+    format!("{:X}", md5::compute(path.as_bytes()))
+}
+
+/// every existing dir mpv could have written watch_later files to;
+/// mpvshelf's own config dir first, then mpv's platform defaults.
+pub fn watch_later_dirs(config_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![config_dir.join("watch_later")];
+
+    #[cfg(windows)]
+    if let Some(appdata) = env::var_os("APPDATA") {
+        dirs.push(PathBuf::from(appdata).join("mpv").join("watch_later"));
+    }
+
+    #[cfg(not(windows))]
+    if let Some(home) = env::var_os("HOME").map(PathBuf::from) {
+        // mpv >= 0.36 keeps watch_later in the state dir
+        let state_dir = env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".local").join("state"));
+        dirs.push(state_dir.join("mpv").join("watch_later"));
+        dirs.push(home.join(".config").join("mpv").join("watch_later"));
+    }
+
+    dirs.retain(|dir| dir.is_dir());
+    dirs.dedup();
+    dirs
+}
+
+fn parse_watch_later_file(path: &Path) -> Result<WatchLaterEntry, io::Error> {
+    let modified = fs::metadata(path)?.modified().ok();
+    let mut start = None;
+    let mut tracks = TrackSelection::default();
+    let mut speed = None;
+
+    for line in fs::read_to_string(path)?.lines() {
+        // the first line is a `# <path>` comment
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            match key {
+                "start" => start = value.parse::<f64>().ok(),
+                "aid" => tracks.aid = parse_track(value),
+                "sid" => tracks.sid = parse_track(value),
+                "speed" => speed = value.parse::<f64>().ok(),
+                _ => {}
+            }
+        }
+    }
+
+    Ok(WatchLaterEntry {
+        start,
+        tracks,
+        speed,
+        modified,
+    })
+}
+
+/// `aid=2`, `sid=no`, `sid=auto`
+fn parse_track(value: &str) -> Option<Track> {
+    match value {
+        "no" => Some(Track::Off),
+        _ => value.parse::<i64>().ok().map(Track::Id),
+    }
+}
+
+/// finds the newest watch_later file for `video_path` across all `dirs`
+pub fn find_watch_later_entry(dirs: &[PathBuf], video_path: &str) -> Option<WatchLaterEntry> {
+    let hash = watch_later_hash(video_path);
+    dirs.iter()
+        .map(|dir| dir.join(&hash))
+        .filter(|path| path.is_file())
+        .filter_map(|path| parse_watch_later_file(&path).ok())
+        .max_by_key(|entry| entry.modified)
+}
+
+/// how a watch_later position counts towards finishing a video,
+/// the same as a position saved during playback
+pub struct Completion<'a> {
+    pub policy: &'a CompletionPolicy,
+    /// the `credits_offset` of every main folder, keyed by path
+    pub credits_offsets: HashMap<String, u64>,
+}
+
+impl<'a> Completion<'a> {
+    /// looks up the folders of `os_videos` for their `credits_offset`
+    pub fn new(handle: &AppHandle, user: &'a User, os_videos: &[OsVideo]) -> Self {
+        let mut credits_offsets = HashMap::new();
+        for vid in os_videos {
+            if credits_offsets.contains_key(&vid.main_folder_path) {
+                continue;
+            }
+            let credits_offset =
+                get_os_folder_by_path(handle.clone(), vid.main_folder_path.clone())
+                    .ok()
+                    .and_then(|folder| folder.credits_offset)
+                    .unwrap_or(0);
+            credits_offsets.insert(vid.main_folder_path.clone(), credits_offset);
+        }
+
+        Completion {
+            policy: &user.settings.completion,
+            credits_offsets,
+        }
+    }
+}
+
+/// returns whether `vid` was updated from the `entry`
+fn merge_entry(
+    vid: &mut OsVideo,
+    entry: &WatchLaterEntry,
+    policy: &WatchLaterPolicy,
+    completion: &Completion,
+) -> bool {
+    let Some(start) = entry.start.map(|start| start as u64) else {
+        return false;
+    };
+    if start == vid.position {
+        return false;
+    }
+
+    let db_wins = match policy {
+        // the database has no progress to protect
+        _ if vid.position == 0 => false,
+        WatchLaterPolicy::DbWins => true,
+        WatchLaterPolicy::NewestWins => match (vid.position_time, entry.modified) {
+            (Some(db), Some(watch_later)) => db >= watch_later,
+            _ => true,
+        },
+    };
+    if db_wins {
+        return false;
+    }
+
+    vid.position = start;
+    vid.position_time = entry.modified;
+    let credits_offset = completion
+        .credits_offsets
+        .get(&vid.main_folder_path)
+        .copied()
+        .unwrap_or(0);
+    if completion
+        .policy
+        .is_complete(start, vid.duration, credits_offset)
+    {
+        vid.watched = true;
+        vid.in_progress = false;
+    } else if start > 0 && !vid.watched {
+        vid.in_progress = true;
+    }
+    // the tracks & speed were saved with the position
+    if entry.tracks != TrackSelection::default() {
+        vid.tracks
+            .get_or_insert_with(Default::default)
+            .update(&entry.tracks);
+    }
+    // only used for audiobooks, like the speed mpvshelf saves itself
+    if entry.speed.is_some() {
+        vid.speed = entry.speed;
+    }
+    true
+}
+
+/// merges watch_later state into `os_videos` in place,
+/// and returns the videos that changed.
+pub fn merge_watch_later(
+    os_videos: &mut [OsVideo],
+    dirs: &[PathBuf],
+    policy: &WatchLaterPolicy,
+    completion: &Completion,
+) -> Vec<OsVideo> {
+    if dirs.is_empty() {
+        return Vec::new();
+    }
+
+    os_videos
+        .iter_mut()
+        .filter_map(|vid| {
+            let entry = find_watch_later_entry(dirs, &vid.path)?;
+            merge_entry(vid, &entry, policy, completion).then(|| vid.clone())
+        })
+        .collect()
+}
+
+/// merges watch_later state into freshly scanned videos,
+/// when the user has turned on `Settings.watch_later_import`.
+pub fn merge_scanned_watch_later(
+    handle: &AppHandle,
+    user: &User,
+    os_videos: &mut [OsVideo],
+) -> Result<(), WatchLaterError> {
+    let Some(policy) = &user.settings.watch_later_import else {
+        return Ok(());
+    };
+
+    let app_data_dir = handle.path().app_data_dir()?;
+    let dirs = watch_later_dirs(&user.settings.mpv_settings.config_dir(&app_data_dir));
+    if dirs.is_empty() {
+        return Ok(());
+    }
+    let completion = Completion::new(handle, user, os_videos);
+    merge_watch_later(os_videos, &dirs, policy, &completion);
+
+    Ok(())
+}
+
+/// imports watch_later state for every video the user has,
+/// returns the videos that were updated.
+#[command]
+pub fn import_watch_later(
+    handle: AppHandle,
+    user: User,
+    policy: WatchLaterPolicy,
+) -> Result<Vec<OsVideo>, WatchLaterError> {
+    let app_data_dir = handle.path().app_data_dir()?;
+    let dirs = watch_later_dirs(&user.settings.mpv_settings.config_dir(&app_data_dir));
+
+    let mut os_videos = get_os_videos_by_user(&handle, &user.id)?;
+    let completion = Completion::new(&handle, &user, &os_videos);
+    let merged = merge_watch_later(&mut os_videos, &dirs, &policy, &completion);
+    update_os_videos(handle, merged.clone())?;
+
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// as written by mpv 0.37 for `--save-position-on-quit`
+    const WATCH_LATER_FILE: &str = "# /home/user/Videos/show/01.mkv
+start=612.345000
+speed=1.250000
+volume=80.000000
+sid=no
+aid=2
+";
+
+    fn at(secs: u64) -> Option<SystemTime> {
+        Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    fn entry(modified: u64) -> WatchLaterEntry {
+        WatchLaterEntry {
+            start: Some(612.345),
+            tracks: TrackSelection {
+                aid: Some(Track::Id(2)),
+                sid: Some(Track::Off),
+                audio_lang: None,
+                sub_lang: None,
+            },
+            speed: Some(1.25),
+            modified: at(modified),
+        }
+    }
+
+    fn played_video(position_time: u64) -> OsVideo {
+        OsVideo {
+            position: 300,
+            position_time: at(position_time),
+            ..OsVideo::stub("/home/user/Videos/show", "/home/user/Videos/show/01.mkv")
+        }
+    }
+
+    #[test]
+    fn hashes_paths_like_mpv_names_its_watch_later_files() {
+        assert_eq!(
+            watch_later_hash("/home/user/Videos/show/01.mkv"),
+            "BBFC6C19A73B2A3C898C4101D54E534A"
+        );
+        assert_eq!(
+            watch_later_hash(r"C:\Videos\show\01.mkv"),
+            "CE7D765CD7CAB04CA444CA61B6A5E072"
+        );
+    }
+
+    #[test]
+    fn parses_a_watch_later_file() {
+        let dir = env::temp_dir().join(format!("mpvshelf-watch-later-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let hash = watch_later_hash("/home/user/Videos/show/01.mkv");
+        fs::write(dir.join(&hash), WATCH_LATER_FILE).unwrap();
+
+        let found =
+            find_watch_later_entry(std::slice::from_ref(&dir), "/home/user/Videos/show/01.mkv");
+        let _ = fs::remove_dir_all(&dir);
+
+        let found = found.unwrap();
+        assert_eq!(found.start, Some(612.345));
+        assert_eq!(found.speed, Some(1.25));
+        assert_eq!(found.tracks, entry(0).tracks);
+        assert!(found.modified.is_some());
+    }
+
+    #[test]
+    fn newest_position_wins() {
+        let policy = WatchLaterPolicy::NewestWins;
+        let completion = Completion {
+            policy: &CompletionPolicy::default(),
+            credits_offsets: HashMap::new(),
+        };
+
+        let mut vid = played_video(2000);
+        assert!(!merge_entry(&mut vid, &entry(1000), &policy, &completion));
+        assert_eq!(vid.position, 300);
+
+        let mut vid = played_video(1000);
+        assert!(merge_entry(&mut vid, &entry(2000), &policy, &completion));
+        assert_eq!(vid.position, 612);
+        assert_eq!(vid.position_time, at(2000));
+        assert_eq!(vid.speed, Some(1.25));
+        assert_eq!(vid.tracks.map(|tracks| tracks.sid), Some(Some(Track::Off)));
+
+        // positions saved before mpvshelf kept their time
+        let mut vid = OsVideo {
+            position_time: None,
+            ..played_video(0)
+        };
+        assert!(!merge_entry(&mut vid, &entry(2000), &policy, &completion));
+    }
+
+    #[test]
+    fn db_wins_unless_it_has_no_position() {
+        let policy = WatchLaterPolicy::DbWins;
+        let completion = Completion {
+            policy: &CompletionPolicy::default(),
+            credits_offsets: HashMap::new(),
+        };
+
+        let mut vid = played_video(1000);
+        assert!(!merge_entry(&mut vid, &entry(2000), &policy, &completion));
+
+        let mut vid = OsVideo {
+            position: 0,
+            ..played_video(1000)
+        };
+        assert!(merge_entry(&mut vid, &entry(0), &policy, &completion));
+        assert_eq!(vid.position, 612);
+    }
+
+    #[test]
+    fn merged_positions_count_towards_completion() {
+        let policy = WatchLaterPolicy::NewestWins;
+        let percentage = CompletionPolicy::Percentage(90);
+        let mut completion = Completion {
+            policy: &percentage,
+            credits_offsets: HashMap::new(),
+        };
+
+        // 612 of 1440 seconds
+        let mut vid = OsVideo {
+            duration: 1440,
+            ..played_video(1000)
+        };
+        assert!(merge_entry(&mut vid, &entry(2000), &policy, &completion));
+        assert!(vid.in_progress && !vid.watched);
+
+        // 612 of 660 seconds
+        let mut vid = OsVideo {
+            duration: 660,
+            in_progress: true,
+            ..played_video(1000)
+        };
+        assert!(merge_entry(&mut vid, &entry(2000), &policy, &completion));
+        assert!(vid.watched && !vid.in_progress);
+
+        // 612 of 720 seconds, only without the last 60 of credits
+        let mut vid = OsVideo {
+            duration: 720,
+            ..played_video(1000)
+        };
+        completion
+            .credits_offsets
+            .insert(vid.main_folder_path.clone(), 60);
+        assert!(merge_entry(&mut vid, &entry(2000), &policy, &completion));
+        assert!(vid.watched);
+    }
+}
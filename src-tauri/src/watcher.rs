@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use hashbrown::HashSet;
+use notify::event::{AccessKind, AccessMode, ModifyKind};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::database::data::v2::OsFolder;
+use crate::database::{get_os_folder_by_path, get_root_os_folders, get_user_by_id};
+use crate::error::{MpvShelfError, WatcherError};
+use crate::fs::{apply_library_diff, preview_library_diff};
+
+///! Keeps the library in sync with the filesystem.
+///! Every root folder is watched recursively (inotify on linux),
+///! & the folders that changed are rescanned once the changes settle.
+///! New & changed files are added right away, emitting `library-changed`.
+///! Removals are only emitted as a `LibraryDiff` with `library-removals`,
+///! a drive being unmounted looks the same as its folders being deleted,
+///! so they wait until the user confirms them with `apply_library_diff`.
+
+/// how long the filesystem has to be quiet before rescanning,
+/// so copying a season only rescans once.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+pub struct LibraryWatcher(Mutex<Option<WatchedRoots>>);
+
+struct WatchedRoots {
+    watcher: RecommendedWatcher,
+    paths: HashSet<String>,
+}
+
+/// starts watching the root folders added since the last call,
+/// & stops watching the removed ones.
+pub fn watch_library(handle: &AppHandle) -> Result<(), WatcherError> {
+    let state = handle.state::<LibraryWatcher>();
+    let mut roots = state.0.lock().unwrap();
+    if roots.is_none() {
+        let (tx, rx) = mpsc::channel();
+        let watcher = notify::recommended_watcher(tx)?;
+        let handle = handle.clone();
+        thread::spawn(move || follow_changes(handle, rx));
+        *roots = Some(WatchedRoots {
+            watcher,
+            paths: HashSet::new(),
+        });
+    }
+    let roots = roots.as_mut().unwrap();
+
+    let root_paths: HashSet<String> = get_root_os_folders(handle)?
+        .into_iter()
+        .map(|folder| folder.path)
+        .collect();
+
+    for path in roots.paths.difference(&root_paths) {
+        // the folder may be gone already, which also removes the watch
+        let _ = roots.watcher.unwatch(Path::new(path));
+    }
+    roots.paths.retain(|path| root_paths.contains(path));
+
+    for path in root_paths {
+        if roots.paths.contains(&path) {
+            continue;
+        }
+        match roots
+            .watcher
+            .watch(Path::new(&path), RecursiveMode::Recursive)
+        {
+            Ok(()) => {
+                roots.paths.insert(path);
+            }
+            Err(e) => eprintln!("failed to watch {path}: {e}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// files being created, removed or renamed,
+/// & files that finished being written, so their duration is read again.
+fn is_library_change(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_)
+            | EventKind::Remove(_)
+            | EventKind::Modify(ModifyKind::Name(_))
+            | EventKind::Access(AccessKind::Close(AccessMode::Write))
+    )
+}
+
+fn follow_changes(handle: AppHandle, rx: Receiver<notify::Result<Event>>) {
+    let mut changed_dirs: BTreeSet<PathBuf> = BTreeSet::new();
+    loop {
+        let received = if changed_dirs.is_empty() {
+            rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            rx.recv_timeout(DEBOUNCE)
+        };
+
+        match received {
+            Ok(Ok(event)) if is_library_change(&event.kind) => {
+                changed_dirs.extend(
+                    event
+                        .paths
+                        .iter()
+                        .filter_map(|path| path.parent().map(Path::to_path_buf)),
+                );
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => eprintln!("library watcher error: {e}"),
+            Err(RecvTimeoutError::Timeout) => {
+                sync_changed_dirs(&handle, std::mem::take(&mut changed_dirs))
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+}
+
+/// rescans the closest library folder of every changed dir.
+/// new dirs aren't in the library yet, so their parent picks them up.
+fn sync_changed_dirs(handle: &AppHandle, changed_dirs: BTreeSet<PathBuf>) {
+    let mut folders: BTreeMap<String, OsFolder> = BTreeMap::new();
+    for dir in changed_dirs {
+        let folder = dir.ancestors().find_map(|path| {
+            get_os_folder_by_path(handle.clone(), path.to_string_lossy().to_string()).ok()
+        });
+        if let Some(folder) = folder {
+            folders.insert(folder.path.clone(), folder);
+        }
+    }
+
+    for (path, folder) in folders {
+        match sync_os_folder(handle, folder) {
+            Ok(true) => {
+                if let Err(e) = handle.emit("library-changed", &path) {
+                    eprintln!("failed to emit library-changed: {e}");
+                }
+            }
+            Ok(false) => {}
+            Err(e) => eprintln!("failed to sync {path}: {e}"),
+        }
+    }
+}
+
+/// applies the additions & refreshes of the folder's diff,
+/// & leaves its removals for the user to confirm
+fn sync_os_folder(handle: &AppHandle, folder: OsFolder) -> Result<bool, MpvShelfError> {
+    let user = get_user_by_id(handle.clone(), folder.user_id.clone())?;
+    let mut diff = preview_library_diff(
+        handle.clone(),
+        folder.path,
+        folder.parent_path,
+        user.clone(),
+    )?;
+
+    let removals = diff.take_removals();
+    if !removals.is_empty() {
+        if let Err(e) = handle.emit("library-removals", &removals) {
+            eprintln!("failed to emit library-removals: {e}");
+        }
+    }
+
+    tauri::async_runtime::block_on(apply_library_diff(handle.clone(), user, diff, None))
+}
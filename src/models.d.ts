@@ -8,6 +8,9 @@ export type UserType = {
 export type SettingsType = {
   user_id: string;
   mpv_settings: MpvSettings;
+  watch_later_import?: WatchLaterPolicy;
+  completion: CompletionPolicy;
+  ignore_patterns: string[];
   update_date: string;
   update_time: string;
 };
@@ -17,8 +20,21 @@ export type MpvSettingsType = {
 	config_path: string | undefined;
   plugins_path?: string;
   autoplay: boolean;
+  checkpoint_interval: number;
+  resume: ResumeMode;
+  resume_chapter_start: boolean;
+  resume_tail: number;
+  backend: PlayerBackendKind;
 };
 
+export type PlayerBackendKind = "mpv" | { command_template: string[] };
+
+export type ResumeMode = "always" | "ask" | "never";
+
+export type WatchLaterPolicy = "newest_wins" | "db_wins";
+
+export type CompletionPolicy = { percentage: number } | { from_end: number };
+
 export type UserFormType = {
   username: string;
 }
@@ -31,10 +47,24 @@ export type OsFolder = {
   os_videos: OsVideo[];
   last_watched_video?: OsVideo;
   cover_img_path: string | undefined;
+  credits_offset?: number;
+  profile?: PlaybackProfile;
+  tracks?: TrackSelection;
+  audiobook: boolean;
   update_date: string;
   update_time: string;
 }
 
+export type PlaybackProfile = {
+  audio_lang?: string;
+  sub_lang?: string;
+  subtitles?: boolean;
+  speed?: number;
+  volume?: number;
+  extra_args: string[];
+  autoplay?: boolean;
+}
+
 export type OsVideo = {
   user_id: string;
   main_folder_path: string;
@@ -42,18 +72,45 @@ export type OsVideo = {
   title: string;
   cover_img_path: string | undefined;
   metadata: FileMetadata;
+  subtitles: Subtitle[];
+  tracks?: TrackSelection;
+  chapters: Chapter[];
+  speed?: number;
   watched: bool;
+  in_progress: boolean;
   duration: number;
   position: number;
+  position_time: number | null;
   update_date: string;
   update_time: string;
 }
 
+export type Track = { id: number } | "off";
+
+export type TrackSelection = {
+  aid?: Track;
+  sid?: Track;
+  audio_lang?: string;
+  sub_lang?: string;
+}
+
+export type Chapter = {
+  title?: string;
+  start: number;
+  end: number;
+}
+
+export type Subtitle = {
+  path: string;
+  lang?: string;
+}
+
 export type FileMetadata = {
   created: number;
   modified: number;
   accessed: number;
   size: number;
+  fingerprint?: string;
 }
 
 export type FolderMetadata = {
@@ -67,3 +124,68 @@ export type FolderContains = {
 }
 
 export type SortType = "none" | "updated" | "episode_title_regex";
+
+export type MpvPlugin = {
+  name: string;
+  kind: "script" | "script_opts";
+  source_path: string;
+  installed_path: string;
+  enabled: boolean;
+  install_date: string;
+  install_time: string;
+}
+
+export type MpvVersion = {
+  major: number;
+  minor: number;
+  patch: number;
+}
+
+export type MpvCapabilities = {
+  path: string;
+  version?: MpvVersion;
+  libplacebo_version?: string;
+  ffmpeg_version?: string;
+  ipc: boolean;
+  lua: boolean;
+}
+
+export type Bookmark = {
+  video_path: string;
+  timestamp: number;
+  name: string;
+  note: string;
+  create_date: string;
+  create_time: string;
+}
+
+export type SleepTimer = { minutes: number } | "end_of_episode" | { episodes: number };
+
+export type SleepAction = "pause" | "quit";
+
+export type ShuffleMode = "all" | "unwatched" | "folder_tree";
+
+export type Shuffle = {
+  mode: ShuffleMode;
+  seed?: number;
+}
+
+export type ScanProgress = {
+  scan_id: string;
+  dirs_visited: number;
+  videos_found: number;
+  fingerprints_done: number;
+  fingerprints_total: number;
+  thumbnails_done: number;
+  thumbnails_total: number;
+};
+
+export type LibraryDiff = {
+  dir: string;
+  parent_path?: string;
+  added_folders: string[];
+  added_videos: string[];
+  removed_folders: string[];
+  removed_videos: string[];
+  refreshed_videos: string[];
+};
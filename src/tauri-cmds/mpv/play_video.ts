@@ -1,7 +1,7 @@
 import { invoke } from "@tauri-apps/api/core";
-import { OsFolder, OsVideo, UserType } from "../../models";
+import { Bookmark, OsFolder, Shuffle, OsVideo, SortType, UserType } from "../../models";
 
-export default async function play_video(mainFolder: OsFolder, osVideos: OsVideo[], video: OsVideo, user: UserType) {
+export default async function play_video(mainFolder: OsFolder, osVideos: OsVideo[], video: OsVideo, user: UserType, sortType?: SortType, bookmark?: Bookmark, shuffle?: Shuffle) {
   console.log(`playing video: ${video}`);
-  await invoke("play_video", { mainFolder, osVideos, video, user });
+  await invoke("play_video", { mainFolder, osVideos, video, user, sortType, bookmark, shuffle });
 }
@@ -0,0 +1,10 @@
+import { invoke } from "@tauri-apps/api/core";
+import { LibraryDiff, UserType } from "../../models";
+
+export default async function apply_library_diff(
+  user: UserType,
+  diff: LibraryDiff,
+  scanId?: string
+) {
+  return await invoke("apply_library_diff", { user, diff, scanId }) as boolean;
+}
@@ -0,0 +1,5 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export default async function cancel_scan(scanId: string) {
+  return await invoke("cancel_scan", { scanId }) as boolean;
+}
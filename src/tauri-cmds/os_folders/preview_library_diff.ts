@@ -0,0 +1,10 @@
+import { invoke } from "@tauri-apps/api/core";
+import { LibraryDiff, UserType } from "../../models";
+
+export default async function preview_library_diff(
+  dir: string,
+  parentPath: string | undefined,
+  user: UserType
+) {
+  return await invoke("preview_library_diff", { dir, parentPath, user }) as LibraryDiff;
+}
@@ -6,8 +6,9 @@ export default async function upsert_read_os_dir(
   parentPath: String | undefined,
   user: UserType,
   oldDirs: OsFolder[] | undefined,
-  oldVideos: OsVideo[] | undefined
+  oldVideos: OsVideo[] | undefined,
+  scanId?: string
 ) {
-  return await invoke("upsert_read_os_dir", { dir, parentPath, user, oldDirs, oldVideos }) as boolean;
+  return await invoke("upsert_read_os_dir", { dir, parentPath, user, oldDirs, oldVideos, scanId }) as boolean;
 }
 
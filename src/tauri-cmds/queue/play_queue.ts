@@ -0,0 +1,6 @@
+import { invoke } from "@tauri-apps/api/core";
+import { UserType } from "../../models";
+
+export default async function play_queue(user: UserType, videoPath?: string) {
+  await invoke("play_queue", { user, videoPath });
+}